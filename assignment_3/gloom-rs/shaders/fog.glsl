@@ -0,0 +1,44 @@
+// Distance fog and height haze, with the parameters of Fog in uniform_buffer.rs
+#ifndef FOG_GLSL
+#define FOG_GLSL
+
+#include "frame.glsl"
+
+#define FOG_NONE 0
+#define FOG_LINEAR 1
+#define FOG_EXPONENTIAL 2
+#define FOG_EXPONENTIAL_SQUARED 3
+
+// How much of the light from a point reaches the camera through the fog, from 0 to 1
+float fog_visibility(vec3 position)
+{
+    vec3 ray = position - camera_position.xyz;
+    float distance = length(ray);
+
+    float visibility = 1.0;
+    if (fog_mode == FOG_LINEAR) {
+        visibility = clamp((fog_end - distance) / (fog_end - fog_start), 0.0, 1.0);
+    } else if (fog_mode == FOG_EXPONENTIAL) {
+        visibility = exp(-fog_density * distance);
+    } else if (fog_mode == FOG_EXPONENTIAL_SQUARED) {
+        float amount = fog_density * distance;
+        visibility = exp(-amount * amount);
+    }
+
+    if (height_fog_density > 0.0) {
+        // Density integrated along the ray, for a density falling off exponentially with height
+        float falloff = height_fog_falloff * ray.y;
+        float along_ray = abs(falloff) > 0.0001 ? (1.0 - exp(-falloff)) / falloff : 1.0;
+        float at_camera = height_fog_density * exp(-height_fog_falloff * (camera_position.y - height_fog_base));
+        visibility *= exp(-at_camera * along_ray * distance);
+    }
+    return visibility;
+}
+
+// Fade the color of a surface at position into the fog
+vec3 apply_fog(vec3 color, vec3 position)
+{
+    return mix(fog_color.rgb, color, fog_visibility(position));
+}
+
+#endif
@@ -0,0 +1,22 @@
+// Per-frame data, filled once per frame from FrameData in uniform_buffer.rs
+#ifndef FRAME_GLSL
+#define FRAME_GLSL
+
+layout(std140, binding = 0) uniform Frame
+{
+    mat4 view;
+    mat4 projection;
+    mat4 view_projection;
+    vec4 camera_position;
+    float time;
+    int fog_mode;
+    float fog_start;
+    float fog_end;
+    vec4 fog_color;
+    float fog_density;
+    float height_fog_density;
+    float height_fog_base;
+    float height_fog_falloff;
+};
+
+#endif
@@ -0,0 +1,145 @@
+// Shared lighting code, included by the scene shaders
+#ifndef LIGHTING_GLSL
+#define LIGHTING_GLSL
+
+#include "frame.glsl"
+
+// Has to match MAX_LIGHTS in light.rs
+#define MAX_LIGHTS 16
+
+#define DIRECTIONAL_LIGHT 0
+#define POINT_LIGHT 1
+#define SPOT_LIGHT 2
+
+// A light in world space, filled from GpuLight in light.rs
+struct Light
+{
+    vec4 position;    // xyz: position, w: kind of light
+    vec4 direction;   // xyz: direction the light shines in
+    vec4 color;       // rgb: color times intensity
+    vec4 attenuation; // x: constant, y: linear, z: quadratic
+    vec4 cone;        // x: cosine of the inner angle, y: cosine of the outer angle
+};
+
+// Every light in the scene, filled once per frame from LightData in light.rs
+layout(std140, binding = 1) uniform Lights
+{
+    vec4 ambient;
+    int light_count;
+    int environment_levels;
+    Light lights[MAX_LIGHTS];
+};
+
+// Environment around the scene, bound to ENVIRONMENT_MAP_UNIT in light.rs when environment_levels isn't 0
+layout(binding = 14) uniform samplerCube environment_map;
+
+// Has to match MAX_CASCADES in shadow.rs
+#define MAX_CASCADES 4
+
+// Shadows of the main light, filled once per frame from ShadowData in shadow.rs
+layout(std140, binding = 2) uniform Shadows
+{
+    mat4 light_view_projection[MAX_CASCADES];
+    vec4 cascade_far;
+    int cascade_count;
+    int shadow_light;
+    int pcf_radius;
+    float normal_bias;
+    float depth_bias;
+};
+
+// One layer of depth per cascade, bound to SHADOW_MAP_UNIT in shadow.rs
+layout(binding = 15) uniform sampler2DArrayShadow shadow_map;
+
+// How much of the main light reaches a point, from 0 in full shadow to 1 when fully lit
+float shadow_factor(vec3 position, vec3 normal, vec3 to_light)
+{
+    float depth = -(view * vec4(position, 1.0)).z;
+    if (depth > cascade_far[cascade_count - 1]) {
+        return 1.0;
+    }
+    int cascade = cascade_count - 1;
+    for (int i = 0; i < cascade_count; i++) {
+        if (depth < cascade_far[i]) {
+            cascade = i;
+            break;
+        }
+    }
+
+    // Push the point away from the surface, further at grazing angles where acne is worst
+    vec3 biased = position + normal * normal_bias * (1.0 - max(dot(normal, to_light), 0.0));
+    vec4 light_space = light_view_projection[cascade] * vec4(biased, 1.0);
+    vec3 coords = light_space.xyz / light_space.w * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0).xy);
+    float lit = 0.0;
+    for (int x = -pcf_radius; x <= pcf_radius; x++) {
+        for (int y = -pcf_radius; y <= pcf_radius; y++) {
+            vec2 offset = vec2(float(x), float(y)) * texel;
+            lit += texture(shadow_map, vec4(coords.xy + offset, float(cascade), coords.z - depth_bias));
+        }
+    }
+    float samples = float((2 * pcf_radius + 1) * (2 * pcf_radius + 1));
+    return lit / samples;
+}
+
+// Light arriving from the environment in a direction, blurred more for rougher surfaces by reading smaller mipmaps.
+// Without an environment map, the ambient light comes equally from every direction.
+vec3 environment_light(vec3 direction, float roughness)
+{
+    if (environment_levels == 0) {
+        return ambient.rgb;
+    }
+    return textureLod(environment_map, direction, roughness * float(environment_levels - 1)).rgb;
+}
+
+// Light from light i reaching a point, after attenuation, the spot cone and shadows. Sets to_light to the direction
+// towards the light.
+vec3 incoming_light(int i, vec3 position, vec3 normal, out vec3 to_light)
+{
+    Light light = lights[i];
+    int kind = int(light.position.w);
+
+    to_light = -normalize(light.direction.xyz);
+    float attenuation = 1.0;
+    if (kind != DIRECTIONAL_LIGHT) {
+        vec3 offset = light.position.xyz - position;
+        float distance = length(offset);
+        to_light = offset / distance;
+        attenuation = 1.0 / (light.attenuation.x + light.attenuation.y * distance + light.attenuation.z * distance * distance);
+    }
+    if (kind == SPOT_LIGHT) {
+        float cos_angle = dot(-to_light, normalize(light.direction.xyz));
+        attenuation *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+    }
+    if (i == shadow_light) {
+        attenuation *= shadow_factor(position, normal, to_light);
+    }
+    return light.color.rgb * attenuation;
+}
+
+// Blinn-Phong shading of a point on a surface, lit by the ambient light and every light in the scene
+vec3 blinn_phong(vec3 position, vec3 normal, vec3 albedo, float specular_strength, float shininess)
+{
+    vec3 to_camera = normalize(camera_position.xyz - position);
+    vec3 result = environment_light(normal, 1.0) * albedo;
+
+    for (int i = 0; i < light_count; i++) {
+        vec3 to_light;
+        vec3 light = incoming_light(i, position, normal, to_light);
+
+        float diffuse = max(dot(normal, to_light), 0.0);
+        float specular = 0.0;
+        if (diffuse > 0.0) {
+            vec3 halfway = normalize(to_light + to_camera);
+            specular = pow(max(dot(normal, halfway), 0.0), shininess);
+        }
+        result += (albedo * diffuse + specular_strength * specular) * light;
+    }
+    return result;
+}
+
+#endif
@@ -0,0 +1,29 @@
+// Tangent space normal mapping, included by the model shaders
+#ifndef NORMAL_MAP_GLSL
+#define NORMAL_MAP_GLSL
+
+// Set by the material when it has a normal map. normal_strength scales the bumps, and normal_tiling repeats the map
+// over the texture coordinates, e.g. to tile a detail map over a large mesh.
+uniform bool has_normal_texture;
+uniform sampler2D normal_texture;
+uniform float normal_strength;
+uniform float normal_tiling;
+
+// The normal of the surface at a point, bent by the normal map. tangent is the interpolated tangent from the vertex
+// shader, with the handedness of the tangent space in w.
+vec3 surface_normal(vec3 normal, vec4 tangent, vec2 texcoord)
+{
+    vec3 n = normalize(normal);
+    if (!has_normal_texture) {
+        return n;
+    }
+    // Interpolation skews the tangent, so make it orthogonal to the normal again
+    vec3 t = normalize(tangent.xyz - n * dot(n, tangent.xyz));
+    vec3 b = cross(n, t) * tangent.w;
+
+    vec3 bump = texture(normal_texture, texcoord * normal_tiling).xyz * 2.0 - 1.0;
+    bump.xy *= normal_strength;
+    return normalize(mat3(t, b, n) * bump);
+}
+
+#endif
@@ -0,0 +1,10 @@
+#version 430 core
+
+in vec4 vertexColor;
+
+out vec4 color;
+
+void main()
+{
+    color = vertexColor;
+}
\ No newline at end of file
@@ -0,0 +1,123 @@
+#version 430 core
+
+#include "lighting.glsl"
+#include "normal_map.glsl"
+#include "fog.glsl"
+#include "tonemap.glsl"
+
+#define PI 3.14159265
+
+out vec4 out_color;
+
+layout(location = 0) in vec4 color;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec3 world_position;
+layout(location = 3) in vec2 texcoord;
+layout(location = 4) in vec4 tangent;
+
+// Metallic-roughness parameters, set from PbrMaterial in pbr.rs. Each factor is multiplied with its texture,
+// and used on its own when the material has no texture for it.
+uniform vec4 base_color;
+uniform float metallic;
+uniform float roughness;
+uniform vec3 emissive;
+
+uniform bool has_base_color_texture;
+uniform bool has_metallic_texture;
+uniform bool has_roughness_texture;
+uniform bool has_emissive_texture;
+
+uniform sampler2D base_color_texture;
+// Metallic is read from the blue and roughness from the green channel, like the metallicRoughness texture of glTF
+uniform sampler2D metallic_texture;
+uniform sampler2D roughness_texture;
+uniform sampler2D emissive_texture;
+
+// GGX / Trowbridge-Reitz normal distribution
+float distribution_ggx(float n_dot_h, float roughness)
+{
+    float alpha = roughness * roughness;
+    float alpha2 = alpha * alpha;
+    float d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+// Smith's shadowing-masking with the Schlick-GGX approximation for direct light
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness)
+{
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float view = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float light = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return view * light;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0)
+{
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Scale and bias to f0 of the split sum approximation of the specular environment light, using Karis' analytic fit
+// instead of a lookup texture
+vec2 environment_brdf(float n_dot_v, float roughness)
+{
+    const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+    const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+    vec4 r = roughness * c0 + c1;
+    float a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    return vec2(-1.04, 1.04) * a004 + r.zw;
+}
+
+void main()
+{
+    vec4 albedo = color * base_color;
+    float surface_metallic = metallic;
+    float surface_roughness = roughness;
+    vec3 emission = emissive;
+    if (has_base_color_texture) {
+        albedo *= texture(base_color_texture, texcoord);
+    }
+    if (has_metallic_texture) {
+        surface_metallic *= texture(metallic_texture, texcoord).b;
+    }
+    if (has_roughness_texture) {
+        surface_roughness *= texture(roughness_texture, texcoord).g;
+    }
+    if (has_emissive_texture) {
+        emission *= texture(emissive_texture, texcoord).rgb;
+    }
+    // Perfectly smooth surfaces make the highlights of point lights infinitely small
+    surface_roughness = clamp(surface_roughness, 0.04, 1.0);
+
+    vec3 n = surface_normal(normal, tangent, texcoord);
+    vec3 to_camera = normalize(camera_position.xyz - world_position);
+    float n_dot_v = max(dot(n, to_camera), 0.0001);
+    // Dielectrics reflect about 4% head on, metals reflect their own color
+    vec3 f0 = mix(vec3(0.04), albedo.rgb, surface_metallic);
+
+    vec3 result = vec3(0.0);
+    for (int i = 0; i < light_count; i++) {
+        vec3 to_light;
+        vec3 light = incoming_light(i, world_position, n, to_light);
+        float n_dot_l = dot(n, to_light);
+        if (n_dot_l <= 0.0) {
+            continue;
+        }
+
+        vec3 halfway = normalize(to_light + to_camera);
+        float d = distribution_ggx(max(dot(n, halfway), 0.0), surface_roughness);
+        float g = geometry_smith(n_dot_v, n_dot_l, surface_roughness);
+        vec3 f = fresnel_schlick(max(dot(halfway, to_camera), 0.0), f0);
+
+        vec3 specular = d * g * f / (4.0 * n_dot_v * n_dot_l);
+        vec3 diffuse = (1.0 - f) * (1.0 - surface_metallic) * albedo.rgb / PI;
+        // Light colors are the irradiance Blinn-Phong uses, so scale by PI to light both material models equally
+        result += (diffuse + specular) * light * n_dot_l * PI;
+    }
+
+    vec2 brdf = environment_brdf(n_dot_v, surface_roughness);
+    vec3 ambient_specular = environment_light(reflect(-to_camera, n), surface_roughness) * (f0 * brdf.x + brdf.y);
+    vec3 ambient_diffuse = environment_light(n, 1.0) * albedo.rgb * (1.0 - surface_metallic);
+    result += ambient_diffuse + ambient_specular + emission;
+
+    out_color = vec4(tonemap(apply_fog(result, world_position)), albedo.a);
+}
@@ -0,0 +1,7 @@
+#version 430 core
+
+// Only depth is written to the shadow map
+
+void main()
+{
+}
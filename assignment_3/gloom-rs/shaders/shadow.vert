@@ -0,0 +1,13 @@
+#version 430 core
+
+// Depth only pass rendering the scene from the main light into a cascade of the shadow map
+
+layout(location = 0) in vec3 in_position;
+
+uniform mat4 model_matrix;
+uniform mat4 light_view_projection;
+
+void main()
+{
+    gl_Position = light_view_projection * model_matrix * vec4(in_position, 1.0f);
+}
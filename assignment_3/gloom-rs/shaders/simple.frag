@@ -1,14 +1,25 @@
 #version 430 core
 
+#include "lighting.glsl"
+#include "normal_map.glsl"
+#include "fog.glsl"
+#include "tonemap.glsl"
+
 out vec4 out_color;
 
-in vec3 normal;
-in vec4 color;
+layout(location = 1) in vec3 normal;
+layout(location = 0) in vec4 color;
+layout(location = 2) in vec3 world_position;
+layout(location = 3) in vec2 texcoord;
+layout(location = 4) in vec4 tangent;
 
-vec3 lightDirection = normalize(vec3(0.8, -0.5,0.6));
+uniform vec4 base_color;
+uniform float specular_strength;
+uniform float shininess;
 
 void main()
 {
-    vec4 lightColor = color * max(0, dot(normal, -lightDirection));
-    out_color = vec4(lightColor.xyz, color.a);
+    vec4 surface_color = color * base_color;
+    vec3 lit = blinn_phong(world_position, surface_normal(normal, tangent, texcoord), surface_color.rgb, specular_strength, shininess);
+    out_color = vec4(tonemap(apply_fog(lit, world_position)), surface_color.a);
 }
\ No newline at end of file
@@ -1,18 +1,28 @@
 #version 430 core
 
-in vec3 in_position;
-in vec3 in_normal;
-in vec4 in_color;
+#include "frame.glsl"
 
-out vec4 color;
-out vec3 normal;
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec4 in_color;
+layout(location = 3) in vec2 in_texcoord;
+layout(location = 4) in vec4 in_tangent;
+
+layout(location = 0) out vec4 color;
+layout(location = 1) out vec3 normal;
+layout(location = 2) out vec3 world_position;
+layout(location = 3) out vec2 texcoord;
+layout(location = 4) out vec4 tangent;
 
-uniform mat4 vp_matrix;
 uniform mat4 model_matrix;
 
 void main()
 {
     color = in_color;
+    texcoord = in_texcoord;
     normal = normalize(mat3(model_matrix) * in_normal);
-    gl_Position = vp_matrix * model_matrix * vec4(in_position, 1.0f);
+    tangent = vec4(normalize(mat3(model_matrix) * in_tangent.xyz), in_tangent.w);
+    vec4 position = model_matrix * vec4(in_position, 1.0f);
+    world_position = position.xyz;
+    gl_Position = view_projection * position;
 }
\ No newline at end of file
@@ -0,0 +1,15 @@
+#version 430 core
+
+#include "tonemap.glsl"
+
+layout(location = 0) in vec3 direction;
+
+out vec4 out_color;
+
+// Bound to ENVIRONMENT_MAP_UNIT in light.rs, like environment_map in lighting.glsl
+layout(binding = 14) uniform samplerCube environment_map;
+
+void main()
+{
+    out_color = vec4(tonemap(texture(environment_map, direction).rgb), 1.0);
+}
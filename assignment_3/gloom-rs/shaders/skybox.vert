@@ -0,0 +1,16 @@
+#version 430 core
+
+// The sky, drawn as one triangle covering the screen on the far plane
+
+layout(location = 0) out vec3 direction;
+
+// Inverse of the projection times the rotation of the camera, leaving out its position so the sky stays infinitely far away
+uniform mat4 inverse_view_projection;
+
+void main()
+{
+    vec2 position = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2)) * 2.0 - 1.0;
+    vec4 world = inverse_view_projection * vec4(position, 1.0, 1.0);
+    direction = world.xyz / world.w;
+    gl_Position = vec4(position, 1.0, 1.0);
+}
@@ -0,0 +1,16 @@
+// Fitting linear HDR colors into the 0 to 1 range of the framebuffer, which converts them to sRGB when they are written
+#ifndef TONEMAP_GLSL
+#define TONEMAP_GLSL
+
+// Colors above this are compressed, while the ones below are written as they are
+const float TONEMAP_KNEE = 0.8;
+
+// Bright colors approach 1 instead of being clipped, so an HDR sky keeps its gradients. The curve continues the
+// slope of the unchanged range, so there is no visible edge at the knee.
+vec3 tonemap(vec3 color)
+{
+    vec3 over = max(color - TONEMAP_KNEE, 0.0);
+    return min(color, vec3(TONEMAP_KNEE)) + over / (1.0 + over / (1.0 - TONEMAP_KNEE));
+}
+
+#endif
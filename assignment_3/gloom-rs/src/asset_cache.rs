@@ -0,0 +1,202 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use crate::mesh::Mesh;
+use gloom_engine::util::{fnv1a, FNV_OFFSET};
+use gloom_engine::watcher;
+
+// Processed meshes are stored in cache/ as <file stem>-<hash>.mesh, where the hash covers the absolute path and the
+// content of the source file, the content of the material libraries it names, and the loader options. Editing the
+// source or its materials or changing how it is loaded therefore results in a new entry, and files with the same name in different directories get entries of their own. Each entry records
+// the path of its source, and older entries for the same source are removed when the new one is written.
+const CACHE_DIR: &str = "cache";
+// Changed whenever the layout of the file changes, so entries written by older versions are converted again
+const MAGIC: &[u8; 4] = b"GMC4";
+
+// Load the named meshes converted from the file at path, running convert and storing its result if there is no valid cache entry
+pub fn load_or_convert<F>(path: &str, options: &str, convert: F) -> Vec<(String, Mesh)>
+    where F: FnOnce() -> Vec<(String, Mesh)>
+{
+    let source = match fs::read(path) {
+        Ok(source) => source,
+        // Let the loader report the missing file
+        Err(_) => return convert(),
+    };
+
+    let source_path = watcher::canonical(Path::new(path)).to_string_lossy().to_string();
+    let mut hash = fnv1a(FNV_OFFSET, source_path.as_bytes());
+    hash = fnv1a(hash, &source);
+    // The colors of the meshes come from the materials
+    for library in material_libraries(path, &source) {
+        if let Ok(library) = fs::read(library) {
+            hash = fnv1a(hash, &library);
+        }
+    }
+    hash = fnv1a(hash, options.as_bytes());
+    let stem = Path::new(path).file_stem().unwrap().to_string_lossy().to_string();
+    let cache_path = Path::new(CACHE_DIR).join(format!("{}-{:016x}.mesh", stem, hash));
+
+    if let Ok(meshes) = read(&cache_path) {
+        println!("Using cached {}", cache_path.display());
+        return meshes;
+    }
+
+    let meshes = convert();
+    remove_stale(&stem, &source_path, &cache_path);
+    if let Err(e) = write(&cache_path, &source_path, &meshes) {
+        println!("Failed to write asset cache {}: {}", cache_path.display(), e);
+    }
+    meshes
+}
+
+// Remove the named mesh from a list returned by load_or_convert
+pub fn take(meshes: &mut Vec<(String, Mesh)>, name: &str) -> Option<Mesh> {
+    let index = meshes.iter().position(|(n, _)| n == name)?;
+    Some(meshes.remove(index).1)
+}
+
+// The files named by the mtllib lines of an .obj file, which are relative to the directory of the file
+fn material_libraries(path: &str, source: &[u8]) -> Vec<PathBuf> {
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut libraries = vec![];
+    for line in String::from_utf8_lossy(source).lines() {
+        let mut words = line.split_whitespace();
+        if words.next() == Some("mtllib") {
+            libraries.extend(words.map(|name| directory.join(name)));
+        }
+    }
+    libraries
+}
+
+// Remove the entries other than keep that were converted from source. Entries that can't be read, like the ones written
+// by older versions, are removed as well, since they would never be used again.
+fn remove_stale(stem: &str, source: &str, keep: &PathBuf) {
+    if let Ok(entries) = fs::read_dir(CACHE_DIR) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if &path == keep || !is_entry_of(&name, stem) {
+                continue;
+            }
+            if read_source(&path).map_or(true, |entry_source| entry_source == source) {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+// Whether name has the form <stem>-<16 hex digits>.mesh, so files that merely start with the stem are left alone
+fn is_entry_of(name: &str, stem: &str) -> bool {
+    let hash = name.strip_prefix(stem)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.strip_suffix(".mesh"));
+    hash.map_or(false, |hash| hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// The source path recorded in an entry, read without loading the meshes
+fn read_source(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut header = [0; 8];
+    file.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a mesh cache file"));
+    }
+    let mut source = vec![0; u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize];
+    file.read_exact(&mut source)?;
+    Ok(String::from_utf8_lossy(&source).to_string())
+}
+
+fn write(path: &PathBuf, source: &str, meshes: &Vec<(String, Mesh)>) -> io::Result<()> {
+    fs::create_dir_all(CACHE_DIR)?;
+    let mut data = vec![];
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&(source.len() as u32).to_le_bytes());
+    data.extend_from_slice(source.as_bytes());
+    data.extend_from_slice(&(meshes.len() as u32).to_le_bytes());
+
+    for (name, mesh) in meshes {
+        data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        data.extend_from_slice(name.as_bytes());
+        for array in &[&mesh.vertices, &mesh.normals, &mesh.colors, &mesh.texcoords, &mesh.tangents] {
+            data.extend_from_slice(&(array.len() as u32).to_le_bytes());
+            array.iter().for_each(|v| data.extend_from_slice(&v.to_le_bytes()));
+        }
+        data.extend_from_slice(&(mesh.indices.len() as u32).to_le_bytes());
+        mesh.indices.iter().for_each(|i| data.extend_from_slice(&i.to_le_bytes()));
+    }
+
+    // Written next to the entry and renamed into place, so an interrupted write never leaves a truncated entry behind
+    let temporary = path.with_extension(format!("mesh.{}.tmp", std::process::id()));
+    let result = fs::File::create(&temporary)
+        .and_then(|mut file| file.write_all(&data))
+        .and_then(|_| fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+fn read(path: &PathBuf) -> io::Result<Vec<(String, Mesh)>> {
+    let mut data = vec![];
+    fs::File::open(path)?.read_to_end(&mut data)?;
+    let mut reader = Reader { data: &data, position: 0 };
+
+    if reader.bytes(4)? != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a mesh cache file"));
+    }
+    // The source is part of the hash in the file name, so it doesn't have to be checked
+    let source_length = reader.u32()? as usize;
+    reader.bytes(source_length)?;
+
+    let count = reader.u32()?;
+    let mut meshes = vec![];
+    for _ in 0..count {
+        let name_length = reader.u32()? as usize;
+        let name = String::from_utf8_lossy(reader.bytes(name_length)?).to_string();
+        let vertices = reader.f32_array()?;
+        let normals = reader.f32_array()?;
+        let colors = reader.f32_array()?;
+        let texcoords = reader.f32_array()?;
+        let tangents = reader.f32_array()?;
+        let index_count = reader.u32()? as usize;
+        let indices = (0..index_count).map(|_| reader.u32()).collect::<io::Result<Vec<u32>>>()?;
+
+        meshes.push((name, Mesh {
+            vertices,
+            normals,
+            colors,
+            texcoords,
+            tangents,
+            index_count: indices.len() as i32,
+            indices,
+        }));
+    }
+    Ok(meshes)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        if self.position + count > self.data.len() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated mesh cache file"));
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn f32_array(&mut self) -> io::Result<Vec<f32>> {
+        let length = self.u32()? as usize;
+        (0..length).map(|_| self.u32().map(f32::from_bits)).collect()
+    }
+}
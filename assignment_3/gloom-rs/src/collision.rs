@@ -0,0 +1,68 @@
+extern crate nalgebra_glm as glm;
+
+use tobj;
+use std::path::Path;
+
+// A single convex piece of a collision mesh, stored as the planes of its faces
+pub struct ConvexHull {
+    pub points: Vec<glm::Vec3>,
+    planes: Vec<(glm::Vec3, f32)>,
+}
+
+// Collision geometry generated by objConverter. Every object in the file is one convex hull.
+// All queries are in model space, so transform points with the inverse of the node's transformation matrix first.
+pub struct CollisionMesh {
+    pub hulls: Vec<ConvexHull>,
+}
+
+impl ConvexHull {
+    fn from(mesh: &tobj::Mesh) -> Self {
+        let points: Vec<glm::Vec3> = mesh.positions.chunks(3).map(|p| glm::vec3(p[0], p[1], p[2])).collect();
+        let planes = mesh.indices.chunks(3).map(|t| {
+            let a = points[t[0] as usize];
+            let normal = glm::normalize(&glm::cross(&(points[t[1] as usize] - a), &(points[t[2] as usize] - a)));
+            (normal, glm::dot(&normal, &a))
+        }).collect();
+
+        ConvexHull { points, planes }
+    }
+
+    pub fn contains_point(&self, point: &glm::Vec3) -> bool {
+        self.planes.iter().all(|(normal, offset)| glm::dot(normal, point) - offset <= 0.0)
+    }
+
+    // Conservative test, spheres close to an edge or corner of the hull may be reported as intersecting
+    pub fn intersects_sphere(&self, center: &glm::Vec3, radius: f32) -> bool {
+        self.planes.iter().all(|(normal, offset)| glm::dot(normal, center) - offset <= radius)
+    }
+}
+
+impl CollisionMesh {
+    pub fn load(path: &str) -> Self {
+        println!("Loading collision mesh...");
+        let (models, _materials) = tobj::load_obj(path, true).expect("Failed to load collision mesh");
+        println!("Loaded {} convex hulls from {}.", models.len(), path);
+
+        CollisionMesh {
+            hulls: models.iter().map(|m| ConvexHull::from(&m.mesh)).collect(),
+        }
+    }
+
+    // Load the collision mesh stored next to a render mesh, e.g. resources/helicopter.collision.obj for resources/helicopter.obj
+    pub fn load_beside(model_path: &str) -> Option<Self> {
+        let collision_path = Path::new(model_path).with_extension("collision.obj");
+        if collision_path.exists() {
+            Some(CollisionMesh::load(collision_path.to_str().unwrap()))
+        } else {
+            None
+        }
+    }
+
+    pub fn contains_point(&self, point: &glm::Vec3) -> bool {
+        self.hulls.iter().any(|h| h.contains_point(point))
+    }
+
+    pub fn intersects_sphere(&self, center: &glm::Vec3, radius: f32) -> bool {
+        self.hulls.iter().any(|h| h.intersects_sphere(center, radius))
+    }
+}
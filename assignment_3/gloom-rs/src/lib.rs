@@ -0,0 +1,15 @@
+// The scene, mesh and rendering modules of the assignment, shared by the binary in main.rs and the tests under tests/
+#[macro_use]
+extern crate gloom_engine;
+
+pub mod toolbox;
+pub mod particle_system;
+pub mod mesh;
+pub mod scene_graph;
+pub mod collision;
+pub mod asset_cache;
+pub mod uniform_buffer;
+pub mod light;
+pub mod shadow;
+pub mod pbr;
+pub mod skybox;
@@ -0,0 +1,174 @@
+extern crate nalgebra_glm as glm;
+
+use gloom_engine::gl_object::Pod;
+use crate::scene_graph::SceneNode;
+
+// Size of the light array in the Lights block of shaders/lighting.glsl. Lights past it are ignored.
+pub const MAX_LIGHTS: usize = 16;
+
+// Texture unit of the environment cube map in shaders/lighting.glsl, next to SHADOW_MAP_UNIT in shadow.rs
+pub const ENVIRONMENT_MAP_UNIT: u32 = 14;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    Spot,
+}
+
+// A light attached to a SceneNode. Position and direction are relative to the node, so the light moves with it.
+#[derive(Debug, Copy, Clone)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: glm::Vec3,
+    pub direction: glm::Vec3,
+    pub color: glm::Vec3,
+    pub intensity: f32,
+    // Constant, linear and quadratic attenuation over distance. Not used by directional lights.
+    pub attenuation: glm::Vec3,
+    // Angles of the spot cone in radians, from the direction to where the light starts fading out and to where it is gone
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+}
+
+impl Light {
+    // A light infinitely far away, shining in direction, like the sun
+    pub fn directional(direction: glm::Vec3, color: glm::Vec3, intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Directional,
+            position: glm::zero(),
+            direction,
+            color,
+            intensity,
+            attenuation: glm::vec3(1.0, 0.0, 0.0),
+            inner_angle: 0.0,
+            outer_angle: 0.0,
+        }
+    }
+
+    // A light shining in every direction from position, reaching roughly 100 units
+    pub fn point(position: glm::Vec3, color: glm::Vec3, intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Point,
+            position,
+            // Unused, but normalized when uploaded
+            direction: glm::vec3(0.0, -1.0, 0.0),
+            color,
+            intensity,
+            attenuation: glm::vec3(1.0, 0.045, 0.0075),
+            inner_angle: 0.0,
+            outer_angle: 0.0,
+        }
+    }
+
+    // A light shining from position in a cone around direction, e.g. a searchlight
+    pub fn spot(position: glm::Vec3, direction: glm::Vec3, color: glm::Vec3, intensity: f32, inner_angle: f32, outer_angle: f32) -> Light {
+        Light {
+            kind: LightKind::Spot,
+            position,
+            direction,
+            color,
+            intensity,
+            attenuation: glm::vec3(1.0, 0.045, 0.0075),
+            inner_angle,
+            outer_angle,
+        }
+    }
+
+    pub fn with_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Light {
+        self.attenuation = glm::vec3(constant, linear, quadratic);
+        self
+    }
+
+    // The light as it is uploaded, placed in the world by the transformation of its node
+    pub fn to_gpu(&self, transformation: &glm::Mat4) -> GpuLight {
+        let position = transformation * glm::vec4(self.position.x, self.position.y, self.position.z, 1.0);
+        let direction = glm::normalize(&(glm::mat4_to_mat3(transformation) * self.direction));
+        let color = self.color * self.intensity;
+        let kind = match self.kind {
+            LightKind::Directional => 0.0,
+            LightKind::Point => 1.0,
+            LightKind::Spot => 2.0,
+        };
+        GpuLight {
+            position: glm::vec4(position.x, position.y, position.z, kind),
+            direction: glm::vec4(direction.x, direction.y, direction.z, 0.0),
+            color: glm::vec4(color.x, color.y, color.z, 0.0),
+            attenuation: glm::vec4(self.attenuation.x, self.attenuation.y, self.attenuation.z, 0.0),
+            cone: glm::vec4(self.inner_angle.cos(), self.outer_angle.cos(), 0.0, 0.0),
+        }
+    }
+}
+
+// A light in world space, laid out like the Light struct of shaders/lighting.glsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct GpuLight {
+    // The kind is stored in w: 0 for directional, 1 for point and 2 for spot lights
+    pub position: glm::Vec4,
+    pub direction: glm::Vec4,
+    pub color: glm::Vec4,
+    pub attenuation: glm::Vec4,
+    // Cosines of the inner and outer angle
+    pub cone: glm::Vec4,
+}
+
+unsafe impl Pod for GpuLight {}
+
+impl GpuLight {
+    pub fn is_directional(&self) -> bool {
+        self.position.w == 0.0
+    }
+}
+
+// Every light in the scene, laid out like the std140 Lights block of shaders/lighting.glsl
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LightData {
+    pub ambient: glm::Vec4,
+    pub count: i32,
+    // Mipmap levels of the environment map bound to ENVIRONMENT_MAP_UNIT, or 0 to use the ambient light instead
+    pub environment_levels: i32,
+    pub _padding: [i32; 2],
+    pub lights: [GpuLight; MAX_LIGHTS],
+}
+
+unsafe impl Pod for LightData {}
+
+impl LightData {
+    pub fn new(ambient: &glm::Vec3, lights: &[GpuLight]) -> LightData {
+        let count = lights.len().min(MAX_LIGHTS);
+        let mut data = LightData {
+            ambient: glm::vec4(ambient.x, ambient.y, ambient.z, 0.0),
+            count: count as i32,
+            environment_levels: 0,
+            _padding: [0; 2],
+            lights: [GpuLight {
+                position: glm::zero(),
+                direction: glm::zero(),
+                color: glm::zero(),
+                attenuation: glm::zero(),
+                cone: glm::zero(),
+            }; MAX_LIGHTS],
+        };
+        data.lights[..count].copy_from_slice(&lights[..count]);
+        data
+    }
+
+    // Light surfaces from an environment map with the given number of mipmap levels instead of the ambient light.
+    // The map has to be bound to ENVIRONMENT_MAP_UNIT when drawing.
+    pub fn with_environment(mut self, levels: i32) -> LightData {
+        self.environment_levels = levels;
+        self
+    }
+}
+
+// Collect the lights of root and its descendants in world space. The transformations of the nodes have to be up to date.
+pub unsafe fn collect_lights(root: &SceneNode, lights: &mut Vec<GpuLight>) {
+    for light in &root.lights {
+        lights.push(light.to_gpu(&root.current_transformation_matrix));
+    }
+    for &child in &root.children {
+        collect_lights(&*child, lights);
+    }
+}
@@ -1,17 +1,12 @@
-mod toolbox;
-mod particle_system;
-
 extern crate nalgebra_glm as glm;
 
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::{mem, os::raw::c_void, ptr};
 
-mod shader;
-mod util;
-mod mesh;
-mod scene_graph;
-
 use glutin::event::{
     DeviceEvent,
     ElementState::{Pressed, Released},
@@ -20,47 +15,66 @@ use glutin::event::{
     WindowEvent,
 };
 use glutin::event_loop::ControlFlow;
-use crate::particle_system::ParticleSystem;
-use crate::scene_graph::{SceneNode, Node};
-use crate::shader::Shader;
+use gloom_rs::particle_system::{EmitterSettings, ParticleSystem};
+use gloom_rs::scene_graph::{SceneNode, Node};
 use glm::Vec3;
 use std::ops::Neg;
-use crate::toolbox::simple_heading_animation;
-use crate::mesh::Helicopter;
+use gloom_rs::toolbox::simple_heading_animation;
+use gloom_rs::mesh::{Helicopter, HelicopterHandle, Terrain};
+use gloom_rs::uniform_buffer::{UniformBuffer, FrameData, Fog, FogMode};
+use gloom_engine::gl_object::Handle;
+use gloom_engine::vertex_layout::Vertex;
+use gloom_engine::material::{Material, ShaderHandle};
+use gloom_rs::light::{Light, LightData};
+use gloom_rs::shadow::{Perspective, ShadowMap, ShadowSettings};
+use gloom_rs::pbr::{self, PbrMaterial};
+use gloom_engine::texture::TextureOptions;
+use gloom_rs::skybox::Skybox;
+use gloom_engine::{compute, loader, shader, texture, util};
+use gloom_rs::{light, mesh, scene_graph, uniform_buffer};
 
 const SCREEN_W: u32 = 800;
 const SCREEN_H: u32 = 600;
 
+// Radius of the sphere around the player's helicopter that is tested against the collision meshes of the others
+const PLAYER_RADIUS: f32 = 3.0;
+
+// Faces of the sky in the order of the cube map targets, used when there is no resources/sky.hdr panorama
+const SKY_FACES: [&str; 6] = [
+    "resources/sky/right.png",
+    "resources/sky/left.png",
+    "resources/sky/top.png",
+    "resources/sky/bottom.png",
+    "resources/sky/front.png",
+    "resources/sky/back.png",
+];
+
 
 struct HeliController {
     direction: glm::Vec3,
     up: glm::Vec3,
 }
 
-fn create_heli(helicopter: &Helicopter) -> Node {
-    let heli_body_vao = unsafe {
-        setup_vao(&helicopter.body.vertices, &helicopter.body.normals, &helicopter.body.colors, &helicopter.body.indices)
-    };
-
-    let heli_main_rotor_vao = unsafe {
-        setup_vao(&helicopter.main_rotor.vertices, &helicopter.main_rotor.normals, &helicopter.main_rotor.colors, &helicopter.main_rotor.indices)
-    };
-
-    let heli_tail_rotor_vao = unsafe {
-        setup_vao(&helicopter.tail_rotor.vertices, &helicopter.tail_rotor.normals, &helicopter.tail_rotor.colors, &helicopter.tail_rotor.indices)
-    };
+// Create the node hierarchy of a helicopter, with a mesh and a material for each part in the order of the Index impl.
+// The nodes draw nothing until the meshes are loaded.
+fn create_heli(meshes: &HelicopterHandle, materials: &[Handle<Material>]) -> Node {
+    let mut root = SceneNode::new();
+    let mut main = SceneNode::new();
+    let mut tail = SceneNode::new();
+    let mut door = SceneNode::new();
 
-    let heli_door_vao = unsafe {
-        setup_vao(&helicopter.door.vertices, &helicopter.door.normals, &helicopter.door.colors, &helicopter.door.indices)
-    };
 
-    let mut root = SceneNode::from_vao(heli_body_vao, helicopter.body.index_count);
-    let mut main = SceneNode::from_vao(heli_main_rotor_vao, helicopter.main_rotor.index_count);
-    let mut tail = SceneNode::from_vao(heli_tail_rotor_vao, helicopter.tail_rotor.index_count);
-    let mut door = SceneNode::from_vao(heli_door_vao, helicopter.door.index_count);
+    tail.reference_point = Vec3::new(0.35, 2.3, 10.4);
 
+    root.mesh = Some(Rc::clone(&meshes.parts[0]));
+    main.mesh = Some(Rc::clone(&meshes.parts[1]));
+    tail.mesh = Some(Rc::clone(&meshes.parts[2]));
+    door.mesh = Some(Rc::clone(&meshes.parts[3]));
 
-    tail.reference_point = Vec3::new(0.35, 2.3, 10.4);
+    root.material = Some(Handle::clone(&materials[0]));
+    main.material = Some(Handle::clone(&materials[1]));
+    tail.material = Some(Handle::clone(&materials[2]));
+    door.material = Some(Handle::clone(&materials[3]));
 
     root.add_child(&mut main);
     root.add_child(&mut tail);
@@ -69,67 +83,20 @@ fn create_heli(helicopter: &Helicopter) -> Node {
     root
 }
 
-unsafe fn setup_vao(vertices: &Vec<f32>, normals: &Vec<f32>, colors: &Vec<f32>, indices: &Vec<u32>) -> u32 {
-    let mut vao = 0;
-    let mut vbo = 0;
-    let mut ibo = 0;
-
-    // Generate VAO
-    gl::GenVertexArrays(1, &mut vao);
-    gl::BindVertexArray(vao);
-
-    //Generate vertex and index buffers
-    gl::GenBuffers(1, &mut vbo);
-    gl::GenBuffers(1, &mut ibo);
-
-    let mut buffer_data = vec![];
-
-    for i in 0..(vertices.len() / 3 - 1) {
-        buffer_data.push(vertices[&i * 3 + 0]);
-        buffer_data.push(vertices[&i * 3 + 1]);
-        buffer_data.push(vertices[&i * 3 + 2]);
-        buffer_data.push(normals[&i * 3 + 0]);
-        buffer_data.push(normals[&i * 3 + 1]);
-        buffer_data.push(normals[&i * 3 + 2]);
-        buffer_data.push(colors[&i * 4 + 0]);
-        buffer_data.push(colors[&i * 4 + 1]);
-        buffer_data.push(colors[&i * 4 + 2]);
-        buffer_data.push(colors[&i * 4 + 3]);
-    }
-    //Fill vertex buffer
-    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-    gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(&buffer_data), util::pointer_to_array(&buffer_data), gl::STATIC_DRAW);
-
-    // Fill index buffer
-    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
-    gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, util::byte_size_of_array(indices), util::pointer_to_array(indices), gl::STATIC_DRAW);
-
-    // Configure vertex attribute layout
-    gl::EnableVertexAttribArray(0);
-    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 10, ptr::null());
-
-    gl::EnableVertexAttribArray(1);
-    gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 10, (3 * util::size_of::<f32>()) as *const gl::types::GLvoid);
-
-    gl::EnableVertexAttribArray(2);
-    gl::VertexAttribPointer(2, 4, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 10, (6 * util::size_of::<f32>()) as *const gl::types::GLvoid);
-    gl::BindVertexArray(0);
-
-    return vao;
-}
-
-unsafe fn draw_scene(root: &scene_graph::SceneNode, view_projection_matrix: &glm::Mat4, shader: &Shader) {
-    if root.index_count > 0 {
-        gl::BindVertexArray(root.vao_id);
-        shader.activate();
-        shader.set_uniform_mat4("vp_matrix", &view_projection_matrix);
+// Draw the nodes with their materials. Nodes without one use parent_material, and the textures of a node are bound
+// after the ones of its material, replacing material textures in the same slot.
+unsafe fn draw_scene(root: &scene_graph::SceneNode, parent_material: &Material) {
+    let material = root.material.as_deref().unwrap_or(parent_material);
+    if let Some(mesh) = root.mesh.as_ref().map(|mesh| mesh.borrow()).filter(|mesh| mesh.is_loaded()) {
+        let (shader, texture_units) = material.apply();
         shader.set_uniform_mat4("model_matrix", &root.current_transformation_matrix);
+        root.textures.bind(&shader, texture_units);
 
-        gl::DrawElements(gl::TRIANGLES, root.index_count as i32, gl::UNSIGNED_INT, ptr::null());
+        mesh.draw();
     }
 
     for &child in & root.children {
-        draw_scene(&*child, view_projection_matrix, &shader);
+        draw_scene(&*child, material);
     }
 }
 
@@ -153,6 +120,12 @@ unsafe fn update_node_transformations(root: &mut SceneNode, transformation_so_fa
     }
 }
 
+// A point in world space in the model space of node, where the queries of its collision mesh are made
+fn to_model_space(node: &SceneNode, point: &glm::Vec3) -> glm::Vec3 {
+    let local = glm::inverse(&node.current_transformation_matrix) * glm::vec4(point.x, point.y, point.z, 1.0);
+    glm::vec3(local.x, local.y, local.z)
+}
+
 unsafe fn rotate_rotors(heli: &mut SceneNode, delta_time: f32) {
     (*heli.children[0]).rotation += glm::vec3(0.0, 200.0 * &delta_time, 0.0);
     (*heli.children[1]).rotation += glm::vec3(-200.0 * &delta_time, 0.0, 0.0);
@@ -176,7 +149,8 @@ fn main() {
         .with_title("Gloom-rs")
         .with_resizable(false)
         .with_inner_size(glutin::dpi::LogicalSize::new(SCREEN_W, SCREEN_H));
-    let cb = glutin::ContextBuilder::new().with_vsync(true);
+    // The shaders work in linear color, and the sRGB framebuffer encodes their output for the screen
+    let cb = glutin::ContextBuilder::new().with_vsync(true).with_srgb(true);
     let windowed_context = cb.build_windowed(wb, &el).unwrap();
     // Uncomment these if you want to use the mouse for controls, but want it to be confined to the screen and/or invisible.
     //windowed_context.window().set_cursor_grab(true).expect("failed to grab cursor");
@@ -208,6 +182,7 @@ fn main() {
             gl::Disable(gl::MULTISAMPLE);
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
             gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
             gl::DebugMessageCallback(Some(util::debug_callback), ptr::null());
 
@@ -226,32 +201,137 @@ fn main() {
 
 
         // Basic usage of shader helper
-        // The code below returns a shader object, which contains the field .program_id
+        // The code below returns a shader object, which contains the method .program_id()
         // The snippet is not enough to do the assignment, and will need to be modified (outside of just using the correct path), but it only needs to be called once
-        // shader::ShaderBuilder::new().attach_file("./path/to/shader").link();
-        let shader = unsafe {
-            shader::ShaderBuilder::new().attach_file("./shaders/simple.frag").attach_file("./shaders/simple.vert").link()
+        // shader::ShaderBuilder::new().attach_file("./path/to/shader")?.link()?;
+        let shader: ShaderHandle = unsafe {
+            Rc::new(RefCell::new(shader::ShaderBuilder::new()
+                .cache_binary()
+                .attach_file("./shaders/simple.frag")
+                .and_then(|builder| builder.attach_file("./shaders/simple.vert"))
+                .and_then(|builder| builder.link())
+                .unwrap_or_else(|e| panic!("{}", e))))
         };
-
-        let projection = glm::perspective(800 as f32 / SCREEN_H as f32, (3.14 / 180.0) * 60.0, 1.0, 500.0);
-
-        let terrain_mesh = mesh::Terrain::load("resources/lunarsurface.obj");
-        let terrain_vao = unsafe {
-            setup_vao(&terrain_mesh.vertices, &terrain_mesh.normals, &terrain_mesh.colors, &terrain_mesh.indices)
+        shader.borrow().validate_layout(&mesh::MeshVertex::layout());
+
+        // Physically based shading as an alternative to simple.frag, used by the helicopters
+        let pbr_shader: ShaderHandle = unsafe {
+            Rc::new(RefCell::new(shader::ShaderBuilder::new()
+                .cache_binary()
+                .attach_file("./shaders/pbr.frag")
+                .and_then(|builder| builder.attach_file("./shaders/simple.vert"))
+                .and_then(|builder| builder.link())
+                .unwrap_or_else(|e| panic!("{}", e))))
+        };
+        pbr_shader.borrow().validate_layout(&mesh::MeshVertex::layout());
+
+        // Models and textures are parsed on worker threads, and the nodes draw nothing until their meshes are uploaded.
+        // Editing a model or texture in resources/ while running loads it again and swaps it in behind its handles.
+        let mut asset_loader = loader::AssetLoader::new(2);
+        asset_loader.watch("resources", &["obj", "mtl", "myf", "png", "hdr"]);
+
+        // Nodes without a material of their own are drawn with the default material
+        let mut default_material = Material::new(Rc::clone(&shader));
+        default_material.set("base_color", glm::vec4(1.0, 1.0, 1.0, 1.0));
+        default_material.set("specular_strength", 0.1);
+        default_material.set("shininess", 8.0);
+        default_material.set("has_normal_texture", false);
+        default_material.set("normal_strength", 1.0);
+        default_material.set("normal_tiling", 1.0);
+
+        // The small bumps of the lunar surface come from a detail normal map tiled over it, rather than from geometry
+        let mut terrain_material = default_material.clone();
+        let normal_map_path = "resources/lunarsurface_normal.png";
+        if Path::new(normal_map_path).is_file() {
+            let normal_map_options = TextureOptions { anisotropy: 8, ..TextureOptions::default() };
+            let normal_map = unsafe { asset_loader.load_texture(normal_map_path, normal_map_options) };
+            terrain_material.textures.set(texture::NORMAL, normal_map);
+            terrain_material.set("has_normal_texture", true);
+            terrain_material.set("normal_tiling", 0.25);
+        } else {
+            println!("Texture {} not found, drawing the lunar surface without a normal map", normal_map_path);
+        }
+        // Parts of the helicopter are painted with the material of resources/helicopter.mtl named after them, and
+        // parts without one get a metallic paint in their color from PART_COLORS
+        let heli_paint = pbr::load_mtl("resources/helicopter.mtl").unwrap_or_else(|e| {
+            println!("{}, painting the helicopter with the default colors", e);
+            vec![]
+        });
+        let parts = Helicopter::PART_NAMES.iter().zip(Helicopter::PART_COLORS.iter());
+        let heli_materials: Vec<Handle<Material>> = parts.map(|(name, &[r, g, b, a])| {
+            let paint = match heli_paint.iter().find(|(material, _)| material == name) {
+                Some((_, paint)) => paint.clone(),
+                None => PbrMaterial { base_color: glm::vec4(r, g, b, a), metallic: 0.6, roughness: 0.35, ..PbrMaterial::default() },
+            };
+            Handle::new(unsafe { paint.to_material(Rc::clone(&pbr_shader), &mut asset_loader) })
+        }).collect();
+
+        // Camera and lighting data shared by all shaders, uploaded once per frame
+        let mut frame_buffer = unsafe { UniformBuffer::<FrameData>::new(uniform_buffer::FRAME_BINDING) };
+        // Lights of the scene graph in world space, collected and uploaded once per frame
+        let mut lights_buffer = unsafe { UniformBuffer::<LightData>::new(uniform_buffer::LIGHTS_BINDING) };
+        let ambient_light = glm::vec3(0.15, 0.15, 0.15);
+
+        let perspective = Perspective { aspect: 800 as f32 / SCREEN_H as f32, fov_y: (3.14 / 180.0) * 60.0, near: 1.0, far: 500.0 };
+        let projection = perspective.matrix();
+
+        // Shadows of the sun, rendered before the scene every frame
+        let mut shadow_map = unsafe { ShadowMap::new(ShadowSettings::default()).unwrap_or_else(|e| panic!("{}", e)) };
+
+        // Fog hides where the terrain ends at the far plane, and haze fills the low parts of it. Both fade into the sky
+        // near the horizon, or into the background without a sky.
+        let mut fog = Fog {
+            mode: FogMode::Linear,
+            // The 0.163 gray background of the earlier assignments, in linear color
+            color: glm::vec3(0.0227, 0.0227, 0.0227),
+            start: 250.0,
+            end: 480.0,
+            density: 0.004,
+            height_density: 0.02,
+            height_base: 0.0,
+            height_falloff: 0.1,
         };
 
-        let mut root_node = SceneNode::new();
-        let mut terrain_node = SceneNode::from_vao(terrain_vao, terrain_mesh.index_count);
-
-        let helicopter = mesh::Helicopter::load("resources/helicopter.obj");
-
-        let mut heli1 = create_heli(&helicopter);
-        let mut heli2 = create_heli(&helicopter);
-        let mut heli3 = create_heli(&helicopter);
-        let mut heli4 = create_heli(&helicopter);
-        let mut heli5 = create_heli(&helicopter);
+        // Sky around the scene, which the materials reflect as well
+        let skybox = Skybox::load_async(&mut asset_loader, "resources/sky.hdr", &SKY_FACES);
 
-        let mut player_heli = create_heli(&helicopter);
+        let mut root_node = SceneNode::new();
+        let mut terrain_node = SceneNode::from_mesh(Terrain::load_async(&mut asset_loader, "resources/lunarsurface.obj"));
+        terrain_node.material = Some(Handle::new(terrain_material));
+
+        let heli_meshes = Helicopter::load_async(&mut asset_loader, "resources/helicopter.obj");
+        let mut heli1 = create_heli(&heli_meshes, &heli_materials);
+        let mut heli2 = create_heli(&heli_meshes, &heli_materials);
+        let mut heli3 = create_heli(&heli_meshes, &heli_materials);
+        let mut heli4 = create_heli(&heli_meshes, &heli_materials);
+        let mut heli5 = create_heli(&heli_meshes, &heli_materials);
+
+        let mut player_heli = create_heli(&heli_meshes, &heli_materials);
+        // Searchlight under the nose of the player's helicopter, pointing ahead and down
+        player_heli.lights.push(
+            Light::spot(glm::vec3(0.0, 0.5, 1.5), glm::vec3(0.0, -1.0, 1.0), glm::vec3(1.0, 0.95, 0.8), 4.0, 0.25, 0.4)
+                .with_attenuation(1.0, 0.022, 0.0019)
+        );
+        // Exhaust of the player's helicopter, simulated by a compute shader and rising from above the body
+        let mut exhaust = unsafe {
+            let compute = compute::ComputeShader::new("./shaders/particles.comp").unwrap_or_else(|e| panic!("{}", e));
+            let shader = shader::ShaderBuilder::new()
+                .attach_file("./shaders/particles.vert")
+                .and_then(|builder| builder.attach_file("./shaders/particles.frag"))
+                .and_then(|builder| builder.link())
+                .unwrap_or_else(|e| panic!("{}", e));
+            let settings = EmitterSettings {
+                radius: 0.3,
+                lifetime_range: (1.0, 3.0),
+                initial_velocity: glm::vec3(0.0, 2.0, 0.0),
+                spread: 0.5,
+                acceleration: glm::vec3(0.0, 0.5, 0.0),
+                start_color: glm::vec4(0.3, 0.3, 0.3, 0.6),
+                end_color: glm::vec4(0.6, 0.6, 0.6, 0.0),
+                size: 0.15,
+            };
+            ParticleSystem::new(2048, glm::zero(), settings, compute, shader)
+        };
         let mut heli_controller = HeliController { direction: glm::vec3(0.0, 0.0, 1.0), up: glm::vec3(0.0, 1.0, 0.0) };
 
         terrain_node.add_child(&mut heli1);
@@ -262,6 +342,7 @@ fn main() {
         terrain_node.add_child(&mut player_heli);
 
         root_node.add_child(&mut terrain_node);
+        root_node.lights.push(Light::directional(glm::vec3(0.8, -0.5, 0.6), glm::vec3(1.0, 1.0, 1.0), 0.8));
 
         let mut scene_graph = root_node;
 
@@ -286,6 +367,20 @@ fn main() {
             let delta_time = now.duration_since(last_frame_time).as_secs_f32();
             last_frame_time = now;
 
+            // Upload models that finished loading since last frame, and reload changed ones
+            asset_loader.process_uploads();
+            unsafe {
+                shader.borrow_mut().reload_if_changed();
+                pbr_shader.borrow_mut().reload_if_changed();
+                shadow_map.reload_if_changed();
+                exhaust.reload_if_changed();
+                if let Some(skybox) = &mut *skybox.borrow_mut() {
+                    skybox.reload_if_changed();
+                }
+            }
+
+            let previous_position = player_heli.position;
+
             // Handle keyboard input
             if let Ok(keys) = pressed_keys.lock() {
                 for key in keys.iter() {
@@ -351,16 +446,67 @@ fn main() {
                 update_node_transformations(&mut scene_graph, &glm::identity());
             }
 
-            view = glm::look_at(&(&player_heli.current_aboslute_position + &glm::vec3(0.0, 5.0, 15.0)),
+            // The other helicopters are solid, so the player stays where it was if it would fly into one of them
+            let others: [&SceneNode; 5] = [&heli1, &heli2, &heli3, &heli4, &heli5];
+            if let Some(collision) = heli_meshes.collision.borrow().as_ref() {
+                let player_center = glm::vec4_to_vec3(&glm::column(&player_heli.current_transformation_matrix, 3));
+                if others.iter().any(|heli| collision.intersects_sphere(&to_model_space(heli, &player_center), PLAYER_RADIUS)) {
+                    player_heli.position = previous_position;
+                    unsafe {
+                        update_node_transformations(&mut scene_graph, &glm::identity());
+                    }
+                }
+            }
+
+            unsafe {
+                exhaust.position = &player_heli.current_aboslute_position + &glm::vec3(0.0, 1.5, 0.0);
+                exhaust.tick(delta_time);
+            }
+
+            // Pull the chase camera in towards the player while it would be inside one of the other helicopters
+            let camera_offset = glm::vec3(0.0, 5.0, 15.0);
+            let inside_heli = |position: &glm::Vec3| heli_meshes.collision.borrow().as_ref().map_or(false, |collision| {
+                others.iter().any(|heli| collision.contains_point(&to_model_space(heli, position)))
+            });
+            let camera_position = (0..10)
+                .map(|i| &player_heli.current_aboslute_position + &camera_offset * (1.0 - i as f32 / 10.0))
+                .find(|position| !inside_heli(position))
+                .unwrap_or(&player_heli.current_aboslute_position + &camera_offset);
+            view = glm::look_at(&camera_position,
                                 &player_heli.current_aboslute_position,
                                 &glm::vec3(0.0, 1.0, 0.0),
             );
 
             unsafe {
-                gl::ClearColor(0.163, 0.163, 0.163, 1.0);
+                // The fog fades into the sky, which changes when the sky is reloaded
+                let skybox = skybox.borrow();
+                if let Some(skybox) = &*skybox {
+                    fog.color = skybox.horizon_color;
+                }
+                frame_buffer.update(&FrameData::new(&view, &projection, &camera_position, elapsed, &fog));
+
+                let mut lights = vec![];
+                light::collect_lights(&scene_graph, &mut lights);
+                let light_data = LightData::new(&ambient_light, &lights);
+                match &*skybox {
+                    Some(skybox) => {
+                        skybox.bind_environment();
+                        lights_buffer.update(&light_data.with_environment(skybox.levels()));
+                    }
+                    None => lights_buffer.update(&light_data),
+                }
+
+                shadow_map.update(&lights, &view, &perspective);
+                shadow_map.render(&scene_graph);
+
+                gl::ClearColor(fog.color.x, fog.color.y, fog.color.z, 1.0);
                 gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
-                draw_scene(&scene_graph, &(&projection * &view), &shader);
+                draw_scene(&scene_graph, &default_material);
+                if let Some(skybox) = &*skybox {
+                    skybox.draw(&view, &projection);
+                }
+                exhaust.render();
             }
 
             context.swap_buffers().unwrap();
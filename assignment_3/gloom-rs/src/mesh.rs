@@ -1,13 +1,47 @@
+extern crate nalgebra_glm as glm;
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::ptr;
+use std::rc::Rc;
 use tobj;
+use gloom_engine::gl_object::{Handle, Pod, VertexArray};
+use gloom_engine::loader::AssetLoader;
+use crate::collision::CollisionMesh;
+use crate::asset_cache;
 
 fn generate_color_vec(color: [f32; 4], num: usize) -> Vec<f32> {
     color.iter().cloned().cycle().take(num*4).collect()
 }
 
+// A vertex of a Mesh, as it is stored in the vertex buffer
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MeshVertex {
+    pub position: glm::Vec3,
+    pub normal: glm::Vec3,
+    pub color: glm::Vec4,
+    pub texcoord: glm::Vec2,
+    // Direction of increasing u along the surface, with the handedness of the tangent space in w
+    pub tangent: glm::Vec4,
+}
+
+unsafe impl Pod for MeshVertex {}
+
+vertex_layout!(MeshVertex {
+    position => "in_position" @ 0,
+    normal => "in_normal" @ 1,
+    color => "in_color" @ 2,
+    texcoord => "in_texcoord" @ 3,
+    tangent => "in_tangent" @ 4,
+});
+
 pub struct Mesh {
     pub vertices: Vec<f32>,
     pub normals: Vec<f32>,
     pub colors: Vec<f32>,
+    pub texcoords: Vec<f32>,
+    pub tangents: Vec<f32>,
     pub indices: Vec<u32>,
     pub index_count: i32,
 }
@@ -16,14 +50,107 @@ impl Mesh {
     pub fn from(mesh: tobj::Mesh, color: [f32; 4]) -> Self {
         let num_verts = mesh.positions.len() / 3;
         let index_count = mesh.indices.len() as i32;
+        // Models without texture coordinates get them projected from above, which suits terrain and keeps textures
+        // and normal maps working on other models
+        let texcoords = if mesh.texcoords.is_empty() {
+            mesh.positions.chunks(3).flat_map(|p| vec![p[0], p[2]]).collect()
+        } else {
+            mesh.texcoords
+        };
+        let tangents = generate_tangents(&mesh.positions, &mesh.normals, &texcoords, &mesh.indices);
         Mesh {
             vertices: mesh.positions,
             normals: mesh.normals,
+            texcoords,
+            tangents,
             indices: mesh.indices,
             colors: generate_color_vec(color, num_verts),
             index_count,
         }
     }
+
+    // Interleave positions, normals, colors and texture coordinates into the vertices uploaded to the GPU
+    pub fn packed_vertices(&self) -> Vec<MeshVertex> {
+        (0..self.vertices.len() / 3).map(|i| MeshVertex {
+            position: glm::vec3(self.vertices[i * 3], self.vertices[i * 3 + 1], self.vertices[i * 3 + 2]),
+            normal: glm::vec3(self.normals[i * 3], self.normals[i * 3 + 1], self.normals[i * 3 + 2]),
+            color: glm::vec4(self.colors[i * 4], self.colors[i * 4 + 1], self.colors[i * 4 + 2], self.colors[i * 4 + 3]),
+            texcoord: glm::vec2(self.texcoords[i * 2], self.texcoords[i * 2 + 1]),
+            tangent: glm::vec4(self.tangents[i * 4], self.tangents[i * 4 + 1], self.tangents[i * 4 + 2], self.tangents[i * 4 + 3]),
+        }).collect()
+    }
+}
+
+// A mesh uploaded to the GPU. Nodes drawing it share it through a MeshHandle, and the loader replaces the VAO behind
+// the handle when the mesh is loaded or reloaded, so the nodes never have to be touched.
+pub struct GpuMesh {
+    pub vao: Option<Handle<VertexArray>>,
+    pub index_count: i32,
+}
+
+pub type MeshHandle = Rc<RefCell<GpuMesh>>;
+
+impl GpuMesh {
+    // A handle to a mesh that draws nothing, standing in for one that is still loading
+    pub fn placeholder() -> MeshHandle {
+        Rc::new(RefCell::new(GpuMesh { vao: None, index_count: 0 }))
+    }
+
+    pub unsafe fn upload(&mut self, mesh: &Mesh) {
+        self.vao = Some(Handle::new(VertexArray::from_vertices(&mesh.packed_vertices(), &mesh.indices)));
+        self.index_count = mesh.index_count;
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.vao.is_some() && self.index_count > 0
+    }
+
+    // Draw the triangles of the mesh with the active program
+    pub unsafe fn draw(&self) {
+        if let Some(vao) = &self.vao {
+            vao.bind();
+            gl::DrawElements(gl::TRIANGLES, self.index_count, gl::UNSIGNED_INT, ptr::null());
+        }
+    }
+}
+
+// Tangents for normal mapping, 4 floats per vertex. The tangent of each triangle follows increasing u, and is summed
+// over the triangles sharing a vertex before it is made orthogonal to the normal. w is -1 where the texture is mirrored.
+fn generate_tangents(positions: &[f32], normals: &[f32], texcoords: &[f32], indices: &[u32]) -> Vec<f32> {
+    let position = |i: usize| glm::vec3(positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2]);
+    let texcoord = |i: usize| glm::vec2(texcoords[i * 2], texcoords[i * 2 + 1]);
+    let vertex_count = positions.len() / 3;
+    let mut tangents = vec![glm::vec3(0.0, 0.0, 0.0); vertex_count];
+    let mut bitangents = vec![glm::vec3(0.0, 0.0, 0.0); vertex_count];
+
+    for triangle in indices.chunks_exact(3) {
+        let (a, b, c) = (triangle[0] as usize, triangle[1] as usize, triangle[2] as usize);
+        let (edge1, edge2) = (position(b) - position(a), position(c) - position(a));
+        let (delta1, delta2) = (texcoord(b) - texcoord(a), texcoord(c) - texcoord(a));
+        let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+        if determinant.abs() < 1e-12 {
+            continue;
+        }
+        let tangent = (edge1 * delta2.y - edge2 * delta1.y) / determinant;
+        let bitangent = (edge2 * delta1.x - edge1 * delta2.x) / determinant;
+        for &vertex in &[a, b, c] {
+            tangents[vertex] += tangent;
+            bitangents[vertex] += bitangent;
+        }
+    }
+
+    (0..vertex_count).flat_map(|i| {
+        let normal = glm::vec3(normals[i * 3], normals[i * 3 + 1], normals[i * 3 + 2]);
+        let mut tangent = tangents[i] - normal * glm::dot(&normal, &tangents[i]);
+        if glm::length(&tangent) < 1e-6 {
+            // The texture is degenerate around this vertex, so any direction along the surface will do
+            let axis = if normal.x.abs() < 0.9 { glm::vec3(1.0, 0.0, 0.0) } else { glm::vec3(0.0, 1.0, 0.0) };
+            tangent = glm::cross(&normal, &axis);
+        }
+        let tangent = glm::normalize(&tangent);
+        let handedness = if glm::dot(&glm::cross(&normal, &tangent), &bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+        vec![tangent.x, tangent.y, tangent.z, handedness]
+    }).collect()
 }
 
 pub struct Terrain;
@@ -31,15 +158,34 @@ impl Terrain {
     pub fn load(path: &str) -> Mesh {
         println!("Loading terrain model...");
         let before = std::time::Instant::now();
-        let (models, _materials) = tobj::load_obj(path, true).expect("Failed to load terrain model");
+        let mut meshes = asset_cache::load_or_convert(path, "triangulate;color=1,1,1,1", || {
+            let (models, _materials) = tobj::load_obj(path, true).expect("Failed to load terrain model");
+            if models.len() > 1 || models.len() == 0 { panic!("Please use a model with a single mesh") }
+
+            let terrain = models[0].to_owned();
+            vec![(terrain.name, Mesh::from(terrain.mesh, [1.0, 1.0, 1.0, 1.0]))]
+        });
         let after = std::time::Instant::now();
         println!("Done in {:.3}ms.", after.duration_since(before).as_micros() as f32 / 1e3);
-        if models.len() > 1 || models.len() == 0 { panic!("Please use a model with a single mesh") }
 
-        let terrain = models[0].to_owned();
-        println!("Loaded {} with {} points and {} triangles.", terrain.name, terrain.mesh.positions.len() /3, terrain.mesh.indices.len() / 3);
+        let (name, terrain) = meshes.remove(0);
+        println!("Loaded {} with {} points and {} triangles.", name, terrain.vertices.len() /3, terrain.indices.len() / 3);
 
-        Mesh::from(terrain.mesh, [1.0, 1.0, 1.0, 1.0])
+        terrain
+    }
+
+    // Load the terrain on a worker thread. The handle draws nothing until the mesh is uploaded, and the mesh is
+    // swapped out behind it when the file changes.
+    pub fn load_async(loader: &mut AssetLoader, path: &str) -> MeshHandle {
+        let handle = GpuMesh::placeholder();
+        let target = Rc::downgrade(&handle);
+        let terrain_path = path.to_string();
+        loader.load_watched(&[path], move || Terrain::load(&terrain_path), move |terrain: Mesh| {
+            if let Some(target) = target.upgrade() {
+                unsafe { target.borrow_mut().upload(&terrain) };
+            }
+        });
+        handle
     }
 }
 
@@ -49,6 +195,7 @@ pub struct Helicopter {
     pub main_rotor: Mesh,
     pub tail_rotor: Mesh,
     pub door: Mesh,
+    pub collision: Option<CollisionMesh>,
 }
 
 // You can use square brackets to access the components of the helicopter, if you want to use loops!
@@ -65,28 +212,70 @@ impl Index<usize> for Helicopter {
     }
 }
 
+// The uploaded parts of a helicopter in the order of the Index impl, and its collision mesh, shared by every
+// helicopter in the scene. Both are empty until the model is loaded.
+#[derive(Clone)]
+pub struct HelicopterHandle {
+    pub parts: Vec<MeshHandle>,
+    pub collision: Rc<RefCell<Option<CollisionMesh>>>,
+}
+
 impl Helicopter {
+    // Names of the body, main rotor, tail rotor and door in the model file
+    pub const PART_NAMES: [&'static str; 4] = ["Body_body", "Main_Rotor_main_rotor", "Tail_Rotor_tail_rotor", "Door_door"];
+
+    // Colors of the body, main rotor, tail rotor and door. The meshes are white, and the colors are given to their materials.
+    pub const PART_COLORS: [[f32; 4]; 4] = [
+        [0.3, 0.3, 0.3, 1.0],
+        [0.3, 0.1, 0.1, 1.0],
+        [0.1, 0.3, 0.1, 1.0],
+        [0.1, 0.1, 0.3, 1.0],
+    ];
+
     pub fn load(path: &str) -> Self {
         println!("Loading helicopter model...");
         let before = std::time::Instant::now();
-        let (models, _materials) = tobj::load_obj(path, true).expect("Failed to load helicopter model");
+        let mut meshes = asset_cache::load_or_convert(path, "triangulate;color=1,1,1,1", || {
+            let (models, _materials) = tobj::load_obj(path, true).expect("Failed to load helicopter model");
+            models.into_iter().map(|m| (m.name.clone(), Mesh::from(m.mesh, [1.0, 1.0, 1.0, 1.0]))).collect()
+        });
         let after = std::time::Instant::now();
         println!("Done in {:.3}ms.", after.duration_since(before).as_micros() as f32 / 1e3);
 
-        for model in &models {
-            println!("Loaded {} with {} points and {} triangles.", model.name, model.mesh.positions.len() / 3, model.mesh.indices.len() / 3);
+        for (name, mesh) in &meshes {
+            println!("Loaded {} with {} points and {} triangles.", name, mesh.vertices.len() / 3, mesh.indices.len() / 3);
         }
 
-        let body_model = models.iter().find(|m| m.name == "Body_body").expect("Incorrect model file!").to_owned();
-        let main_rotor_model = models.iter().find(|m| m.name == "Main_Rotor_main_rotor").expect("Incorrect model file!").to_owned();
-        let tail_rotor_model = models.iter().find(|m| m.name == "Tail_Rotor_tail_rotor").expect("Incorrect model file!").to_owned();
-        let door_model = models.iter().find(|m| m.name == "Door_door").expect("Incorrect model file!").to_owned();
-
         Helicopter {
-            body:       Mesh::from(body_model.mesh,         [0.3, 0.3, 0.3, 1.0]),
-            main_rotor: Mesh::from(main_rotor_model.mesh,   [0.3, 0.1, 0.1, 1.0]),
-            tail_rotor: Mesh::from(tail_rotor_model.mesh,   [0.1, 0.3, 0.1, 1.0]),
-            door:       Mesh::from(door_model.mesh,         [0.1, 0.1, 0.3, 1.0]),
+            body:       asset_cache::take(&mut meshes, Helicopter::PART_NAMES[0]).expect("Incorrect model file!"),
+            main_rotor: asset_cache::take(&mut meshes, Helicopter::PART_NAMES[1]).expect("Incorrect model file!"),
+            tail_rotor: asset_cache::take(&mut meshes, Helicopter::PART_NAMES[2]).expect("Incorrect model file!"),
+            door:       asset_cache::take(&mut meshes, Helicopter::PART_NAMES[3]).expect("Incorrect model file!"),
+            collision:  CollisionMesh::load_beside(path),
         }
     }
+
+    // Load the helicopter on a worker thread, like Terrain::load_async. Editing the model or its collision mesh
+    // loads both again.
+    pub fn load_async(loader: &mut AssetLoader, path: &str) -> HelicopterHandle {
+        let handle = HelicopterHandle {
+            parts: (0..4).map(|_| GpuMesh::placeholder()).collect(),
+            collision: Rc::new(RefCell::new(None)),
+        };
+        let parts = handle.parts.iter().map(Rc::downgrade).collect::<Vec<_>>();
+        let collision = Rc::downgrade(&handle.collision);
+        let model_path = path.to_string();
+        let collision_path = Path::new(path).with_extension("collision.obj").to_string_lossy().to_string();
+        loader.load_watched(&[path, &collision_path], move || Helicopter::load(&model_path), move |mut helicopter: Helicopter| {
+            for (i, part) in parts.iter().enumerate() {
+                if let Some(part) = part.upgrade() {
+                    unsafe { part.borrow_mut().upload(&helicopter[i]) };
+                }
+            }
+            if let Some(collision) = collision.upgrade() {
+                *collision.borrow_mut() = helicopter.collision.take();
+            }
+        });
+        handle
+    }
 }
\ No newline at end of file
@@ -1,144 +1,168 @@
 extern crate nalgebra_glm as glm;
 
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::shader::Shader;
-use crate::{util};
-use core::ptr;
+use gloom_engine::compute::{self, ComputeShader, StorageBuffer};
+use gloom_engine::shader::Shader;
+use gloom_engine::gl_object::{Buffer, Pod, VertexArray};
+use gloom_engine::vertex_layout::Vertex;
+use gloom_engine::util;
 
+// Binding of the particle buffer in shaders/particles.comp
+const PARTICLE_BINDING: u32 = 0;
+
+// A corner of the quad drawn for every particle
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct QuadVertex {
+    position: glm::Vec3,
+}
+
+unsafe impl Pod for QuadVertex {}
+
+vertex_layout!(QuadVertex { position => "position" @ 0 });
+
+// A particle as shaders/particles.comp stores it. The layout matches the std430 Particle struct, where every vec3 is
+// padded to 16 bytes by the float after it. The same buffer is read per instance when the particles are drawn.
+#[repr(C)]
 #[derive(Copy, Clone)]
-struct Particle {
+struct GpuParticle {
     position: glm::Vec3,
+    life: f32,
     velocity: glm::Vec3,
+    lifetime: f32,
     color: glm::Vec4,
-    life: f32,
-    velocity_fun: fn(glm::Vec3, timestep: f32) -> glm::Vec3,
-    color_fun: fn(glm::Vec4, timestep: f32) -> glm::Vec4,
 }
 
+unsafe impl Pod for GpuParticle {}
+
+vertex_layout!(GpuParticle {
+    position => "particle_position" @ 1,
+    color => "particle_color" @ 2,
+});
+
+// How particles are emitted and how they move. Every particle lives for a random time in lifetime_range, starts at a
+// random point within radius of the emitter, and fades from start_color to end_color.
+#[derive(Copy, Clone, Debug)]
+pub struct EmitterSettings {
+    pub radius: f32,
+    pub lifetime_range: (f32, f32),
+    pub initial_velocity: glm::Vec3,
+    // Largest random offset added to each component of the initial velocity
+    pub spread: f32,
+    pub acceleration: glm::Vec3,
+    pub start_color: glm::Vec4,
+    pub end_color: glm::Vec4,
+    // Half the width of a particle
+    pub size: f32,
+}
+
+// Particles simulated on the GPU. Every tick the compute shader moves the particles in a storage buffer and respawns
+// the dead ones around the emitter, and the buffer is then drawn as instance data without going through the CPU.
 pub struct ParticleSystem {
-    particles: Vec<Particle>,
-    position: glm::Vec3,
-    radius: f32,
-    life_min: f32,
-    life_max: f32,
+    pub position: glm::Vec3,
+    pub settings: EmitterSettings,
+    frames: u32,
+    compute: ComputeShader,
     shader: Shader,
-    vao: u32,
-    vbos: [u32; 2],
+    vao: VertexArray,
+    particles: StorageBuffer<GpuParticle>,
 }
 
-impl Particle {
-    pub fn new<>(position: glm::Vec3, life: f32, velocity_fun: fn(glm::Vec3, f32) -> glm::Vec3, color_fun: fn(glm::Vec4, f32) -> glm::Vec4) -> Self {
-        Self {
-            position,
-            velocity_fun,
-            color_fun,
-            color: color_fun(glm::vec4(0.0, 0.0, 0.0, 1.0), -1.0),
-            velocity: velocity_fun(glm::vec3(0.0, 0.0, 0.0), -1.0),
-            life,
-        }
-    }
+impl ParticleSystem {
+    // The compute shader should be built from shaders/particles.comp, and the shader from shaders/particles.vert
+    pub unsafe fn new(size: usize, position: glm::Vec3, settings: EmitterSettings, compute: ComputeShader, shader: Shader) -> ParticleSystem {
+        // Start the particles at random points of their lives, so they don't all respawn in the same frame
+        let (life_min, life_max) = settings.lifetime_range;
+        let particles = (0..size).map(|_| {
+            let lifetime = util::generate_rng(life_min, life_max);
+            GpuParticle {
+                position,
+                life: util::generate_rng(0.0, lifetime),
+                velocity: settings.initial_velocity,
+                lifetime,
+                color: glm::vec4(0.0, 0.0, 0.0, 0.0),
+            }
+        }).collect::<Vec<GpuParticle>>();
+
+        let quad = [
+            QuadVertex { position: glm::vec3(1.0, 1.0, 0.0) },
+            QuadVertex { position: glm::vec3(-1.0, 1.0, 0.0) },
+            QuadVertex { position: glm::vec3(1.0, -1.0, 0.0) },
+            QuadVertex { position: glm::vec3(-1.0, -1.0, 0.0) },
+        ];
 
-    fn revive_if_dead(mut self, position: glm::Vec3, life: f32) -> Self {
-        if self.life <= 0.0 {
-            self.position = position;
-            self.life = life;
-            self.velocity = (self.velocity_fun)(glm::vec3(0.0, 0.0, 0.0), -1.0);
-            self.color = (self.color_fun)(glm::vec4(0.0, 0.0, 0.0, 0.0), -1.0);
-        }
-        self
-    }
+        let particles = StorageBuffer::new(PARTICLE_BINDING, &particles);
 
-    pub fn update(mut self, timestep: f32) -> Self {
-        self.life -= timestep;
-        self.position += &self.velocity * timestep;
-        self.velocity = (self.velocity_fun)(self.velocity, timestep);
-        self.color = (self.color_fun)(self.color, timestep);
-        self
-    }
-}
+        let mut vao = VertexArray::new();
+        vao.bind();
 
-impl ParticleSystem {
-    pub fn new(size: usize, position: glm::Vec3, radius: f32, life_min: f32, life_max: f32, velocity_fun: fn(glm::Vec3, f32) -> glm::Vec3, color_fun: fn(glm::Vec4, f32) -> glm::Vec4, shader: Shader) -> ParticleSystem {
-        let particles: Vec<Particle> = (0..size).into_iter().map(|_| Particle::new(
-            position + glm::vec3(radius * util::generate_rng(-1.0, 1.0), radius * util::generate_rng(-1.0, 1.0), radius * util::generate_rng(-1.0, 1.0)),
-            util::generate_rng(life_min, life_max),
-            velocity_fun,
-            color_fun,
-        )).collect();
-
-        let mut vao = 0;
-        let mut vbos: [u32; 2] =  [0, 0];
-        let buffer: Vec<f32> = vec![
-            1.0, 1.0, 0.0,
-            -1.0, 1.0, 0.0,
-            1.0, -1.0, 0.0,
-            -1.0, -1.0, 0.0,
+        let quad_buffer = Buffer::new(gl::ARRAY_BUFFER, &quad, gl::STATIC_DRAW);
+        QuadVertex::layout().enable(0);
 
-        ];
-        unsafe {
-            // Generate VAO
-            gl::GenVertexArrays(1, &mut vao);
-            gl::BindVertexArray(vao);
-
-            //Generate vertex and index buffersvbo
-            gl::GenBuffers(1,  &mut vbos[0]);
-            gl::GenBuffers(1,  &mut vbos[1]);
-
-            //Fill vertex buffer
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbos[0]);
-            gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(&buffer), util::pointer_to_array(&buffer), gl::STATIC_DRAW);
-
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 3, ptr::null());
-
-            let particles_buffer = generate_buffer_data(&particles);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbos[1]);
-            gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(&particles_buffer), util::pointer_to_array(&particles_buffer), gl::STREAM_DRAW);
-
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 7, ptr::null());
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribPointer(2, 4, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 7, (3 * util::size_of::<f32>()) as *const gl::types::GLvoid);
-            gl::BindVertexArray(0);
-        }
+        // The quad is used repeatedly, while the particles move down the storage buffer once per instance
+        gl::BindBuffer(gl::ARRAY_BUFFER, particles.buffer.id);
+        GpuParticle::layout().enable(1);
+        gl::BindVertexArray(0);
+        vao.keep_alive(quad_buffer);
 
-        unsafe { ParticleSystem { particles, position: position.clone(), radius, life_min, life_max, shader, vao, vbos } }
+        ParticleSystem { position, settings, frames: 0, compute, shader, vao, particles }
     }
 
-    pub fn tick(&mut self, timestep: f32) {
-        self.particles = self.particles.iter().map(
-            |particle| particle.update(timestep).revive_if_dead(
-                &self.position + glm::vec3(
-                    &self.radius * util::generate_rng(-1.0, 1.0),
-                    &self.radius * util::generate_rng(-1.0, 1.0),
-                    &self.radius * util::generate_rng(-1.0, 1.0)), util::generate_rng(self.life_min, self.life_max))
-        ).collect();
-
-        unsafe {self.updateBuffer()}
+    pub unsafe fn tick(&mut self, timestep: f32) {
+        let settings = &self.settings;
+        let shader = &self.compute.shader;
+        shader.activate();
+        shader.set_uniform("timestep", &timestep);
+        shader.set_uniform("seed", &self.frames);
+        shader.set_uniform("emitter", &self.position);
+        shader.set_uniform("radius", &settings.radius);
+        shader.set_uniform("lifetime_range", &glm::vec2(settings.lifetime_range.0, settings.lifetime_range.1));
+        shader.set_uniform("initial_velocity", &settings.initial_velocity);
+        shader.set_uniform("spread", &settings.spread);
+        shader.set_uniform("acceleration", &settings.acceleration);
+        shader.set_uniform("start_color", &settings.start_color);
+        shader.set_uniform("end_color", &settings.end_color);
+        self.frames = self.frames.wrapping_add(1);
+
+        self.particles.bind();
+        self.compute.dispatch_invocations(self.particles.len() as u32, 1, 1);
+        compute::vertex_barrier();
     }
 
-    unsafe fn updateBuffer(&mut self) {
-        let buffer_data = generate_buffer_data(&self.particles);
-        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbos[1]);
-        gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(&buffer_data), ptr::null(), gl::STREAM_DRAW);
-        gl::BufferSubData(gl::ARRAY_BUFFER, 0, util::byte_size_of_array(&buffer_data), util::pointer_to_array(&buffer_data));
+    // Rebuild the shaders if particles.comp, particles.vert or the fragment shader was edited
+    pub unsafe fn reload_if_changed(&mut self) {
+        self.compute.reload_if_changed();
+        self.shader.reload_if_changed();
     }
 
-    pub unsafe fn render(&self, projection: &glm::Mat4, view: &glm::Mat4){
+    // The camera matrices come from the Frame uniform block, which has to be updated before rendering.
+    // Drawn after the opaque geometry, since the particles are blended with what is behind them.
+    // Blending, face culling and depth writes are restored afterwards.
+    pub unsafe fn render(&self) {
         self.shader.activate();
-        self.shader.set_uniform_mat4("projection", projection);
-        self.shader.set_uniform_mat4("view", view);
-        gl::BindVertexArray(self.vao);
-
-        // Make sure the vertex position buffer is used repeatedly, while particle poistion and color moves down the buffer
-        gl::VertexAttribDivisor(0, 0);
-        gl::VertexAttribDivisor(1, 1);
-        gl::VertexAttribDivisor(2, 1);
+        self.shader.set_uniform("particle_size", &self.settings.size);
+        self.vao.bind();
+
+        let blending = gl::IsEnabled(gl::BLEND) == gl::TRUE;
+        let culling = gl::IsEnabled(gl::CULL_FACE) == gl::TRUE;
+        let mut blend_func = [0; 4];
+        for (value, name) in blend_func.iter_mut().zip(&[gl::BLEND_SRC_RGB, gl::BLEND_DST_RGB, gl::BLEND_SRC_ALPHA, gl::BLEND_DST_ALPHA]) {
+            gl::GetIntegerv(*name, value);
+        }
 
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        gl::DepthMask(gl::FALSE);
+        gl::Disable(gl::CULL_FACE);
         gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, self.particles.len() as i32);
-    }
-}
+        gl::DepthMask(gl::TRUE);
 
-fn generate_buffer_data(particles: &Vec<Particle>) -> Vec<f32> {
-    particles.iter().flat_map(|p| vec!(p.position.x, p.position.y, p.position.z, p.color.x, p.color.y, p.color.z, p.color.w)).collect()
+        let [src_rgb, dst_rgb, src_alpha, dst_alpha] = blend_func;
+        gl::BlendFuncSeparate(src_rgb as u32, dst_rgb as u32, src_alpha as u32, dst_alpha as u32);
+        if !blending {
+            gl::Disable(gl::BLEND);
+        }
+        if culling {
+            gl::Enable(gl::CULL_FACE);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,135 @@
+extern crate nalgebra_glm as glm;
+
+use std::path::Path;
+use gloom_engine::material::{Material, ShaderHandle};
+use gloom_engine::loader::AssetLoader;
+use gloom_engine::texture::{self, TextureFormat, TextureOptions};
+
+// Metallic-roughness parameters of a surface for shaders/pbr.frag, named after the pbrMetallicRoughness material of glTF.
+// Each factor is multiplied with its texture, and used on its own when the texture is missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrMaterial {
+    pub base_color: glm::Vec4,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: glm::Vec3,
+    // Strength of the normal map, the scale of a glTF normalTexture
+    pub normal_scale: f32,
+    pub base_color_texture: Option<String>,
+    // Metallic is read from the blue and roughness from the green channel. A glTF metallicRoughness texture is used
+    // for both, while the grayscale maps of MTL work for either.
+    pub metallic_texture: Option<String>,
+    pub roughness_texture: Option<String>,
+    pub emissive_texture: Option<String>,
+    pub normal_texture: Option<String>,
+}
+
+// A white, non-metallic surface without textures
+impl Default for PbrMaterial {
+    fn default() -> PbrMaterial {
+        PbrMaterial {
+            base_color: glm::vec4(1.0, 1.0, 1.0, 1.0),
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: glm::zero(),
+            normal_scale: 1.0,
+            base_color_texture: None,
+            metallic_texture: None,
+            roughness_texture: None,
+            emissive_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+impl PbrMaterial {
+    // Read a material using the PBR extension of MTL (Pm, Pr, Ke and their maps). Materials without it fall back to
+    // the diffuse color and texture, with a roughness estimated from the shininess. The normal map is map_Bump or bump,
+    // with the strength given by its -bm option.
+    // Texture paths are relative to directory, the directory of the MTL file.
+    pub fn from_mtl(material: &tobj::Material, directory: &Path) -> PbrMaterial {
+        let parameter = |key: &str| material.unknown_param.get(key).map(|value| value.trim());
+        let float = |key: &str| parameter(key).and_then(|value| value.parse::<f32>().ok());
+        let texture = |path: &str| if path.is_empty() { None } else { Some(directory.join(path).to_string_lossy().to_string()) };
+        let map = |key: &str| parameter(key).and_then(|path| path.split_whitespace().last()).and_then(texture);
+
+        let [r, g, b] = material.diffuse;
+        let emissive = parameter("Ke")
+            .map(|value| value.split_whitespace().filter_map(|v| v.parse::<f32>().ok()).collect::<Vec<f32>>())
+            .filter(|values| values.len() == 3)
+            .map_or(glm::zero(), |values| glm::vec3(values[0], values[1], values[2]));
+        // Options of the bump map, like "-bm 0.5", are left in the path by tobj
+        let bump = material.normal_texture.split_whitespace().collect::<Vec<&str>>();
+        let normal_scale = bump.windows(2).find(|option| option[0] == "-bm").and_then(|option| option[1].parse().ok());
+
+        PbrMaterial {
+            base_color: glm::vec4(r, g, b, material.dissolve),
+            metallic: float("Pm").unwrap_or(0.0),
+            roughness: float("Pr").unwrap_or_else(|| (2.0 / (material.shininess + 2.0)).sqrt()),
+            emissive,
+            normal_scale: normal_scale.unwrap_or(1.0),
+            base_color_texture: texture(&material.diffuse_texture),
+            metallic_texture: map("map_Pm"),
+            roughness_texture: map("map_Pr"),
+            emissive_texture: map("map_Ke"),
+            normal_texture: bump.last().and_then(|path| texture(path)),
+        }
+    }
+
+    // Create a material for shader, which should use shaders/pbr.frag. Textures are loaded by the loader, and reloaded
+    // when their images change. Missing textures are reported and left out, so the factors are used on their own.
+    pub unsafe fn to_material(&self, shader: ShaderHandle, loader: &mut AssetLoader) -> Material {
+        let mut material = Material::new(shader);
+        material.set("base_color", self.base_color);
+        material.set("metallic", self.metallic);
+        material.set("roughness", self.roughness);
+        material.set("normal_strength", self.normal_scale);
+        material.set("normal_tiling", 1.0);
+
+        // Colors are stored in sRGB, while metallic, roughness and normals are linear
+        let mut load = |path: &Option<String>, format| path.as_ref().and_then(|path| {
+            if !Path::new(path).is_file() {
+                println!("Texture {} not found", path);
+                return None;
+            }
+            Some(loader.load_texture(path, TextureOptions { format, ..TextureOptions::default() }))
+        });
+        let base_color = load(&self.base_color_texture, TextureFormat::Srgba8);
+        let metallic = load(&self.metallic_texture, TextureFormat::Rgb8);
+        let roughness = load(&self.roughness_texture, TextureFormat::Rgb8);
+        let emissive_texture = load(&self.emissive_texture, TextureFormat::Srgb8);
+        let normal = load(&self.normal_texture, TextureFormat::Rgb8);
+
+        material.set("has_base_color_texture", base_color.is_some());
+        material.set("has_metallic_texture", metallic.is_some());
+        material.set("has_roughness_texture", roughness.is_some());
+        material.set("has_emissive_texture", emissive_texture.is_some());
+        material.set("has_normal_texture", normal.is_some());
+
+        // An emissive texture without an emissive factor glows wherever the texture isn't black
+        let mut emissive = self.emissive;
+        if emissive_texture.is_some() && emissive.iter().all(|&c| c == 0.0) {
+            emissive = glm::vec3(1.0, 1.0, 1.0);
+        }
+        for (slot, loaded) in vec![
+            (texture::BASE_COLOR, base_color),
+            (texture::METALLIC, metallic),
+            (texture::ROUGHNESS, roughness),
+            (texture::EMISSIVE, emissive_texture),
+            (texture::NORMAL, normal),
+        ] {
+            if let Some(texture) = loaded {
+                material.textures.set(slot, texture);
+            }
+        }
+        material.set("emissive", emissive);
+        material
+    }
+}
+
+// The materials of an MTL file by name
+pub fn load_mtl(path: &str) -> Result<Vec<(String, PbrMaterial)>, String> {
+    let (materials, _) = tobj::load_mtl(path).map_err(|e| format!("Failed to load materials {}: {}", path, e))?;
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    Ok(materials.iter().map(|material| (material.name.clone(), PbrMaterial::from_mtl(material, directory))).collect())
+}
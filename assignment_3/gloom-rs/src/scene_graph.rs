@@ -2,6 +2,11 @@ extern crate nalgebra_glm as glm;
 
 use std::mem::ManuallyDrop;
 use std::pin::Pin;
+use gloom_engine::gl_object::Handle;
+use gloom_engine::texture::TextureSet;
+use gloom_engine::material::Material;
+use crate::light::Light;
+use crate::mesh::MeshHandle;
 
 // Used to crete an unholy abomination upon which you should not cast your gaze.
 // This ended up being a necessity due to wanting to keep the code written by students as "straight forward" as possible
@@ -21,8 +26,12 @@ pub struct SceneNode {
     pub current_transformation_matrix: glm::Mat4,
     pub current_aboslute_position: glm::Vec3,
 
-    pub vao_id: u32,
-    pub index_count: i32,
+    // Nodes without a mesh only group and transform their children
+    pub mesh: Option<MeshHandle>,
+    pub textures: TextureSet,
+    // Nodes without a material are drawn with the material of their closest ancestor that has one
+    pub material: Option<Handle<Material>>,
+    pub lights: Vec<Light>,
 
     pub children: Vec<*mut SceneNode>,
 }
@@ -36,12 +45,14 @@ impl SceneNode {
             reference_point: glm::zero(),
             current_transformation_matrix: glm::identity(),
             current_aboslute_position: glm::zero(),
-            vao_id: 0,
-            index_count: -1,
+            mesh: None,
+            textures: TextureSet::new(),
+            material: None,
+            lights: vec![],
             children: vec![],
         })))
     }
-    pub fn from_vao(vao_id: u32, index_count: i32) -> Node {
+    pub fn from_mesh(mesh: MeshHandle) -> Node {
         ManuallyDrop::new(Pin::new(Box::new(SceneNode {
             position: glm::zero(),
             rotation: glm::zero(),
@@ -49,7 +60,10 @@ impl SceneNode {
             reference_point: glm::zero(),
             current_transformation_matrix: glm::identity(),
             current_aboslute_position: glm::zero(),
-            vao_id, index_count,
+            mesh: Some(mesh),
+            textures: TextureSet::new(),
+            material: None,
+            lights: vec![],
             children: vec![],
         })))
     }
@@ -80,8 +94,8 @@ impl SceneNode {
     Reference: [{:.2}, {:.2}, {:.2}]
     Current Transformation Matrix: {}
 }}",
-            self.vao_id,
-            self.index_count,
+            self.mesh.as_ref().and_then(|mesh| mesh.borrow().vao.as_ref().map(|vao| vao.id)).unwrap_or(0),
+            self.mesh.as_ref().map_or(-1, |mesh| mesh.borrow().index_count),
             self.children.len(),
             self.position.x,
             self.position.y,
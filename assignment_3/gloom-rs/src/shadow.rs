@@ -0,0 +1,296 @@
+extern crate nalgebra_glm as glm;
+
+use std::ptr;
+use gloom_engine::gl_object::{Framebuffer, Pod, Texture};
+use crate::light::GpuLight;
+use crate::scene_graph::SceneNode;
+use gloom_engine::shader::{Shader, ShaderBuilder, ShaderError};
+use crate::uniform_buffer::{self, UniformBuffer};
+
+// Size of the cascade arrays in the Shadows block of shaders/lighting.glsl
+pub const MAX_CASCADES: usize = 4;
+
+// Texture unit the shadow map is bound to, matching the binding of shadow_map in shaders/lighting.glsl.
+// Materials bind their textures from unit 0, so they don't collide with it.
+pub const SHADOW_MAP_UNIT: u32 = 15;
+
+// The perspective projection of the camera, which the cascades are fitted to
+#[derive(Debug, Copy, Clone)]
+pub struct Perspective {
+    pub aspect: f32,
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Perspective {
+    pub fn matrix(&self) -> glm::Mat4 {
+        glm::perspective(self.aspect, self.fov_y, self.near, self.far)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ShadowSettings {
+    // Width and height of each cascade in texels
+    pub resolution: i32,
+    // Number of cascades the view frustum is split into, from 1 to MAX_CASCADES. More cascades give sharper shadows
+    // close to the camera on large scenes like the terrain.
+    pub cascades: usize,
+    // Distance from the camera shadows are drawn to. Beyond it everything is lit.
+    pub max_distance: f32,
+    // How far behind the cascades, towards the light, shadow casters are still rendered
+    pub caster_distance: f32,
+    // Blend between uniform (0) and logarithmic (1) distribution of the cascade splits
+    pub split_lambda: f32,
+    // Constant offset subtracted from the depth of the shaded point, against shadow acne on surfaces facing the light
+    pub depth_bias: f32,
+    // Offset of the shaded point along its normal, in world units, against acne on surfaces at grazing angles
+    pub normal_bias: f32,
+    // Depth offset scaled by the slope of the caster, applied with glPolygonOffset when rendering the shadow map
+    pub slope_bias: f32,
+    // The shadow map is sampled (2 * pcf_radius + 1)^2 times around each point, softening the edges of shadows
+    pub pcf_radius: i32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> ShadowSettings {
+        ShadowSettings {
+            resolution: 2048,
+            cascades: 3,
+            max_distance: 300.0,
+            caster_distance: 100.0,
+            split_lambda: 0.75,
+            depth_bias: 0.0005,
+            normal_bias: 0.2,
+            slope_bias: 2.0,
+            pcf_radius: 1,
+        }
+    }
+}
+
+// Shadow data read by the scene shaders, laid out like the std140 Shadows block of shaders/lighting.glsl
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ShadowData {
+    pub light_view_projection: [glm::Mat4; MAX_CASCADES],
+    // View space distance each cascade reaches
+    pub cascade_far: glm::Vec4,
+    pub cascade_count: i32,
+    // Index of the light in the Lights block that casts the shadows, or -1 if no light does
+    pub shadow_light: i32,
+    pub pcf_radius: i32,
+    pub normal_bias: f32,
+    pub depth_bias: f32,
+    pub _padding: [f32; 3],
+}
+
+unsafe impl Pod for ShadowData {}
+
+// Shadows of the main light, the first directional light of the scene. The view frustum is split into cascades, and each
+// is rendered into a layer of a depth texture array from the direction of the light before the scene is drawn.
+pub struct ShadowMap {
+    // The depth texture is allocated for the resolution and number of cascades, so they are only changed through set_settings
+    settings: ShadowSettings,
+    pub shader: Shader,
+    framebuffer: Framebuffer,
+    depth: Texture,
+    buffer: UniformBuffer<ShadowData>,
+    data: ShadowData,
+}
+
+impl ShadowMap {
+    pub unsafe fn new(settings: ShadowSettings) -> Result<ShadowMap, ShaderError> {
+        check_settings(&settings);
+
+        let shader = ShaderBuilder::new()
+            .cache_binary()
+            .attach_file("./shaders/shadow.vert")
+            .and_then(|builder| builder.attach_file("./shaders/shadow.frag"))
+            .and_then(|builder| builder.link())?;
+
+        let (framebuffer, depth) = create_depth_array(&settings);
+
+        let data = ShadowData {
+            light_view_projection: [glm::identity(); MAX_CASCADES],
+            cascade_far: glm::zero(),
+            cascade_count: settings.cascades as i32,
+            shadow_light: -1,
+            pcf_radius: settings.pcf_radius,
+            normal_bias: settings.normal_bias,
+            depth_bias: settings.depth_bias,
+            _padding: [0.0; 3],
+        };
+        let mut buffer = UniformBuffer::new(uniform_buffer::SHADOWS_BINDING);
+        buffer.update(&data);
+
+        Ok(ShadowMap { settings, shader, framebuffer, depth, buffer, data })
+    }
+
+    pub fn settings(&self) -> &ShadowSettings {
+        &self.settings
+    }
+
+    // Change the settings, allocating the depth texture again if the resolution or number of cascades changed.
+    // The new settings are used from the next update.
+    pub unsafe fn set_settings(&mut self, settings: ShadowSettings) {
+        check_settings(&settings);
+        if settings.resolution != self.settings.resolution || settings.cascades != self.settings.cascades {
+            let (framebuffer, depth) = create_depth_array(&settings);
+            self.framebuffer = framebuffer;
+            self.depth = depth;
+        }
+        self.settings = settings;
+    }
+
+    // Fit the cascades to the part of the view frustum within max_distance, as seen from the main light,
+    // and upload the result for the scene shaders. Shadows are turned off if there is no directional light.
+    pub unsafe fn update(&mut self, lights: &[GpuLight], view: &glm::Mat4, perspective: &Perspective) {
+        let settings = self.settings;
+        self.data.shadow_light = lights.iter().position(|light| light.is_directional()).map_or(-1, |i| i as i32);
+        self.data.cascade_count = settings.cascades as i32;
+        self.data.pcf_radius = settings.pcf_radius;
+        self.data.normal_bias = settings.normal_bias;
+        self.data.depth_bias = settings.depth_bias;
+
+        if self.data.shadow_light >= 0 {
+            let direction = glm::vec4_to_vec3(&lights[self.data.shadow_light as usize].direction);
+            let near = perspective.near;
+            let far = settings.max_distance.min(perspective.far);
+            let inverse_view = glm::inverse(view);
+
+            let mut split_near = near;
+            for cascade in 0..settings.cascades {
+                // Practical split scheme, between a uniform and a logarithmic distribution
+                let t = (cascade + 1) as f32 / settings.cascades as f32;
+                let logarithmic = near * (far / near).powf(t);
+                let uniform = near + (far - near) * t;
+                let split_far = settings.split_lambda * logarithmic + (1.0 - settings.split_lambda) * uniform;
+
+                let corners = frustum_corners(perspective, split_near, split_far, &inverse_view);
+                self.data.light_view_projection[cascade] = self.fit_cascade(&corners, &direction);
+                self.data.cascade_far[cascade] = split_far;
+                split_near = split_far;
+            }
+        }
+
+        self.buffer.update(&self.data);
+    }
+
+    // The view projection of the light covering the given frustum corners. The cascade is fitted to a bounding sphere
+    // of the corners, and snapped to whole texels, so shadow edges don't shimmer as the camera moves and turns.
+    fn fit_cascade(&self, corners: &[glm::Vec3; 8], direction: &glm::Vec3) -> glm::Mat4 {
+        let center = corners.iter().fold(glm::zero::<glm::Vec3>(), |sum, corner| sum + corner) / 8.0;
+        let radius = corners.iter().map(|corner| glm::distance(corner, &center)).fold(0.0f32, f32::max).ceil();
+
+        let direction = glm::normalize(direction);
+        let up = if direction.y.abs() > 0.99 { glm::vec3(0.0, 0.0, 1.0) } else { glm::vec3(0.0, 1.0, 0.0) };
+        let eye = center - direction * (radius + self.settings.caster_distance);
+        let light_view = glm::look_at(&eye, &center, &up);
+        let mut light_projection = glm::ortho(-radius, radius, -radius, radius, 0.0, 2.0 * radius + self.settings.caster_distance);
+
+        let half_resolution = self.settings.resolution as f32 / 2.0;
+        let origin = (light_projection * light_view) * glm::vec4(0.0, 0.0, 0.0, 1.0) * half_resolution;
+        light_projection[(0, 3)] += (origin.x.round() - origin.x) / half_resolution;
+        light_projection[(1, 3)] += (origin.y.round() - origin.y) / half_resolution;
+
+        light_projection * light_view
+    }
+
+    // Render the depth of every node into the cascades, and bind the result for the scene shaders.
+    // Call it before drawing the scene. The viewport, framebuffer and face culling are restored afterwards.
+    pub unsafe fn render(&self, root: &SceneNode) {
+        if self.data.shadow_light < 0 {
+            self.depth.bind(SHADOW_MAP_UNIT);
+            return;
+        }
+
+        let mut viewport = [0; 4];
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        let culling = gl::IsEnabled(gl::CULL_FACE) == gl::TRUE;
+
+        self.framebuffer.bind();
+        gl::Viewport(0, 0, self.settings.resolution, self.settings.resolution);
+        gl::Enable(gl::DEPTH_TEST);
+        gl::DepthMask(gl::TRUE);
+        // Thin geometry like the rotors has to cast shadows from both sides
+        gl::Disable(gl::CULL_FACE);
+        gl::Enable(gl::POLYGON_OFFSET_FILL);
+        gl::PolygonOffset(self.settings.slope_bias, 1.0);
+
+        self.shader.activate();
+        for cascade in 0..self.settings.cascades {
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, self.depth.id, 0, cascade as i32);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+            self.shader.set_uniform_mat4("light_view_projection", &self.data.light_view_projection[cascade]);
+            draw_depth(root, &self.shader);
+        }
+
+        gl::Disable(gl::POLYGON_OFFSET_FILL);
+        if culling {
+            gl::Enable(gl::CULL_FACE);
+        }
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        self.depth.bind(SHADOW_MAP_UNIT);
+    }
+
+    pub unsafe fn reload_if_changed(&mut self) -> bool {
+        self.shader.reload_if_changed()
+    }
+}
+
+fn check_settings(settings: &ShadowSettings) {
+    assert!(settings.cascades >= 1 && settings.cascades <= MAX_CASCADES,
+        "Shadow maps need between 1 and {} cascades, not {}", MAX_CASCADES, settings.cascades);
+}
+
+// A depth texture array with a layer per cascade, and a framebuffer to render into its layers
+unsafe fn create_depth_array(settings: &ShadowSettings) -> (Framebuffer, Texture) {
+    let depth = Texture::new(gl::TEXTURE_2D_ARRAY);
+    gl::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, gl::DEPTH_COMPONENT32F as i32, settings.resolution, settings.resolution,
+                   settings.cascades as i32, 0, gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null());
+    // Linear filtering of a comparison sampler blends the results of the 2x2 nearest texels, on top of the PCF in the shader
+    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as i32);
+    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
+    // Everything outside the map is lit
+    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+    gl::TexParameterfv(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_BORDER_COLOR, [1.0f32; 4].as_ptr());
+
+    let framebuffer = Framebuffer::new();
+    gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, depth.id, 0, 0);
+    gl::DrawBuffer(gl::NONE);
+    gl::ReadBuffer(gl::NONE);
+    let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+    assert_eq!(status, gl::FRAMEBUFFER_COMPLETE, "Shadow map framebuffer is incomplete: {:#x}", status);
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+    (framebuffer, depth)
+}
+
+// The corners of the part of the view frustum between near and far, in world space
+fn frustum_corners(perspective: &Perspective, near: f32, far: f32, inverse_view: &glm::Mat4) -> [glm::Vec3; 8] {
+    let tan_y = (perspective.fov_y / 2.0).tan();
+    let tan_x = tan_y * perspective.aspect;
+    let mut corners = [glm::zero(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let distance = if i < 4 { near } else { far };
+        let x = (if i & 1 == 0 { -tan_x } else { tan_x }) * distance;
+        let y = (if i & 2 == 0 { -tan_y } else { tan_y }) * distance;
+        *corner = glm::vec4_to_vec3(&(inverse_view * glm::vec4(x, y, -distance, 1.0)));
+    }
+    corners
+}
+
+unsafe fn draw_depth(root: &SceneNode, shader: &Shader) {
+    if let Some(mesh) = root.mesh.as_ref().map(|mesh| mesh.borrow()).filter(|mesh| mesh.is_loaded()) {
+        shader.set_uniform_mat4("model_matrix", &root.current_transformation_matrix);
+        mesh.draw();
+    }
+
+    for &child in &root.children {
+        draw_depth(&*child, shader);
+    }
+}
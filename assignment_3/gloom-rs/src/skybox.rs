@@ -0,0 +1,125 @@
+extern crate nalgebra_glm as glm;
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use gloom_engine::gl_object::{Handle, Texture, VertexArray};
+use gloom_engine::loader::AssetLoader;
+use crate::light::ENVIRONMENT_MAP_UNIT;
+use gloom_engine::shader::{Shader, ShaderBuilder, ShaderError};
+use gloom_engine::texture::{CubemapData, TextureFormat, TextureOptions, Wrap};
+
+// Holds no skybox until the sky has been loaded by Skybox::load_async
+pub type SkyboxHandle = Rc<RefCell<Option<Skybox>>>;
+
+// A cube map drawn behind the scene, which is also the environment the materials reflect
+pub struct Skybox {
+    pub shader: Shader,
+    pub texture: Handle<Texture>,
+    levels: i32,
+    // Average color around the horizon, which distant geometry fades into
+    pub horizon_color: glm::Vec3,
+    // The triangle is generated in the vertex shader, but drawing still needs a VAO
+    vao: VertexArray,
+}
+
+impl Skybox {
+    // Upload the cube map and load the skybox shaders
+    pub unsafe fn new(data: &CubemapData) -> Result<Skybox, ShaderError> {
+        let shader = ShaderBuilder::new()
+            .cache_binary()
+            .attach_file("./shaders/skybox.vert")
+            .and_then(|builder| builder.attach_file("./shaders/skybox.frag"))
+            .and_then(|builder| builder.link())?;
+
+        // Filter across the edges of the faces, so the blurred mipmaps reflected by rough materials have no seams
+        gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+
+        let mut skybox = Skybox {
+            shader,
+            texture: Handle::new(Texture::new(gl::TEXTURE_CUBE_MAP)),
+            levels: 0,
+            horizon_color: glm::vec3(0.0, 0.0, 0.0),
+            vao: VertexArray::new(),
+        };
+        skybox.set_sky(data);
+        Ok(skybox)
+    }
+
+    // Load the sky on a worker thread, from the HDR panorama at hdr_path, or from the faces if there is no panorama.
+    // The sky is loaded again when the panorama or one of the faces changes, or the panorama is created.
+    pub fn load_async(loader: &mut AssetLoader, hdr_path: &str, faces: &'static [&'static str; 6]) -> SkyboxHandle {
+        let handle: SkyboxHandle = Rc::new(RefCell::new(None));
+        let target = Rc::downgrade(&handle);
+        let panorama_path = hdr_path.to_string();
+        let mut paths = vec![hdr_path];
+        paths.extend(faces.iter());
+
+        loader.load_watched(&paths, move || {
+            if Path::new(&panorama_path).exists() {
+                CubemapData::load_equirectangular(&panorama_path, 512)
+            } else {
+                CubemapData::load_faces(faces, TextureFormat::Srgb8)
+            }
+        }, move |sky: Result<CubemapData, String>| {
+            let target = match target.upgrade() {
+                Some(target) => target,
+                None => return,
+            };
+            let mut skybox = target.borrow_mut();
+            match (sky, &mut *skybox) {
+                (Ok(sky), Some(skybox)) => unsafe { skybox.set_sky(&sky) },
+                (Ok(sky), None) => *skybox = Some(unsafe { Skybox::new(&sky).unwrap_or_else(|e| panic!("{}", e)) }),
+                (Err(e), Some(_)) => println!("{}, keeping the current sky", e),
+                (Err(e), None) => println!("{}, drawing the scene without a sky", e),
+            }
+        });
+        handle
+    }
+
+    // Replace the cube map with a new one
+    pub unsafe fn set_sky(&mut self, data: &CubemapData) {
+        let texture = Texture::new(gl::TEXTURE_CUBE_MAP);
+        let options = TextureOptions { format: data.format, wrap_s: Wrap::ClampToEdge, wrap_t: Wrap::ClampToEdge, ..TextureOptions::default() };
+        data.upload(&texture, &options);
+
+        let [r, g, b] = data.horizon_color();
+        self.texture = Handle::new(texture);
+        self.levels = data.levels();
+        self.horizon_color = glm::vec3(r, g, b);
+    }
+
+    // Mipmap levels of the cube map, for LightData::with_environment
+    pub fn levels(&self) -> i32 {
+        self.levels
+    }
+
+    // Bind the cube map to ENVIRONMENT_MAP_UNIT, where the material shaders read it. They only use it when the lights
+    // are uploaded with LightData::with_environment.
+    pub unsafe fn bind_environment(&self) {
+        self.texture.bind(ENVIRONMENT_MAP_UNIT);
+    }
+
+    // Draw the sky wherever nothing has been drawn yet. Drawn after the opaque geometry, so only the visible parts of
+    // the sky are shaded.
+    pub unsafe fn draw(&self, view: &glm::Mat4, projection: &glm::Mat4) {
+        let rotation = glm::mat3_to_mat4(&glm::mat4_to_mat3(view));
+        self.shader.activate();
+        self.shader.set_uniform_mat4("inverse_view_projection", &glm::inverse(&(projection * rotation)));
+        self.bind_environment();
+
+        // The triangle lies on the far plane, so it passes the depth test only where the depth buffer is still clear
+        gl::DepthFunc(gl::LEQUAL);
+        gl::DepthMask(gl::FALSE);
+        gl::Disable(gl::CULL_FACE);
+        self.vao.bind();
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        gl::Enable(gl::CULL_FACE);
+        gl::DepthMask(gl::TRUE);
+        gl::DepthFunc(gl::LESS);
+    }
+
+    pub unsafe fn reload_if_changed(&mut self) -> bool {
+        self.shader.reload_if_changed()
+    }
+}
@@ -0,0 +1,127 @@
+extern crate nalgebra_glm as glm;
+
+use gloom_engine::gl_object::{Buffer, Pod};
+
+// Binding point of the Frame block declared in shaders/frame.glsl
+pub const FRAME_BINDING: u32 = 0;
+// Binding point of the Lights block declared in shaders/lighting.glsl, filled from light::LightData
+pub const LIGHTS_BINDING: u32 = 1;
+// Binding point of the Shadows block declared in shaders/lighting.glsl, filled from shadow::ShadowData
+pub const SHADOWS_BINDING: u32 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FogMode {
+    None,
+    Linear,
+    Exponential,
+    ExponentialSquared,
+}
+
+// Fog over the distance from the camera, and haze that thickens towards the ground. Both fade to color, which should
+// match the sky near the horizon so distant terrain blends into it. The color is linear, like everything the shaders output.
+#[derive(Debug, Copy, Clone)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub color: glm::Vec3,
+    // Distances where linear fog starts and where it hides everything
+    pub start: f32,
+    pub end: f32,
+    // Density of exponential fog
+    pub density: f32,
+    // Density of the haze at height_base, thinning out exponentially above it by height_falloff per unit of height.
+    // A density of 0 turns the haze off.
+    pub height_density: f32,
+    pub height_base: f32,
+    pub height_falloff: f32,
+}
+
+// No fog and no haze
+impl Default for Fog {
+    fn default() -> Fog {
+        Fog {
+            mode: FogMode::None,
+            color: glm::zero(),
+            start: 0.0,
+            end: 1.0,
+            density: 0.0,
+            height_density: 0.0,
+            height_base: 0.0,
+            height_falloff: 1.0,
+        }
+    }
+}
+
+// Per-frame data shared by every shader that includes frame.glsl.
+// The layout has to match the std140 block in the shader, so vec3s are stored as vec4s and the size is a multiple of 16 bytes.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FrameData {
+    pub view: glm::Mat4,
+    pub projection: glm::Mat4,
+    pub view_projection: glm::Mat4,
+    pub camera_position: glm::Vec4,
+    pub time: f32,
+    pub fog_mode: i32,
+    pub fog_start: f32,
+    pub fog_end: f32,
+    pub fog_color: glm::Vec4,
+    pub fog_density: f32,
+    pub height_fog_density: f32,
+    pub height_fog_base: f32,
+    pub height_fog_falloff: f32,
+}
+
+impl FrameData {
+    pub fn new(view: &glm::Mat4, projection: &glm::Mat4, camera_position: &glm::Vec3, time: f32, fog: &Fog) -> FrameData {
+        FrameData {
+            view: *view,
+            projection: *projection,
+            view_projection: projection * view,
+            camera_position: glm::vec4(camera_position.x, camera_position.y, camera_position.z, 1.0),
+            time,
+            fog_mode: match fog.mode {
+                FogMode::None => 0,
+                FogMode::Linear => 1,
+                FogMode::Exponential => 2,
+                FogMode::ExponentialSquared => 3,
+            },
+            fog_start: fog.start,
+            fog_end: fog.end,
+            fog_color: glm::vec4(fog.color.x, fog.color.y, fog.color.z, 1.0),
+            fog_density: fog.density,
+            height_fog_density: fog.height_density,
+            height_fog_base: fog.height_base,
+            height_fog_falloff: fog.height_falloff,
+        }
+    }
+}
+
+unsafe impl Pod for FrameData {}
+
+// A uniform buffer holding one T, bound to a fixed binding point so every program declaring the block at that binding sees it.
+// T has to be #[repr(C)] and laid out like the std140 block it is read as.
+pub struct UniformBuffer<T: Pod> {
+    pub buffer: Buffer<T>,
+    pub binding: u32,
+}
+
+impl<T: Pod> UniformBuffer<T> {
+    pub unsafe fn new(binding: u32) -> UniformBuffer<T> {
+        let buffer = Buffer::with_length(gl::UNIFORM_BUFFER, 1, gl::DYNAMIC_DRAW);
+        gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, buffer.id);
+        gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+
+        UniformBuffer { buffer, binding }
+    }
+
+    // Replace the content of the buffer. Called once per frame, before anything is drawn.
+    pub unsafe fn update(&mut self, value: &T) {
+        self.buffer.update(0, std::slice::from_ref(value));
+        gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+    }
+
+    // Bind the buffer to its binding point again, in case something else was bound there in the meantime
+    pub unsafe fn bind(&self) {
+        gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, self.buffer.id);
+    }
+}
@@ -0,0 +1,59 @@
+// Reading PBR materials from MTL files, with and without the PBR extension of MTL
+
+extern crate nalgebra_glm as glm;
+
+use std::path::Path;
+
+use gloom_rs::pbr::PbrMaterial;
+
+const MTL: &str = "
+newmtl painted_metal
+Kd 0.8 0.1 0.1
+d 0.5
+Pm 0.9
+Pr 0.2
+Ke 0.5 0.25 0
+map_Kd paint.png
+map_Pm -s 2 2 1 metallic.png
+map_Pr roughness.png
+map_Ke glow.png
+map_Bump -bm 0.5 normal.png
+
+newmtl plain
+Kd 0.5 0.5 0.5
+Ns 14
+";
+
+fn parse(name: &str) -> PbrMaterial {
+    let (materials, names) = tobj::load_mtl_buf(&mut MTL.as_bytes()).expect("Failed to parse the test MTL");
+    PbrMaterial::from_mtl(&materials[names[name]], Path::new("resources/textures"))
+}
+
+#[test]
+fn pbr_extension() {
+    let texture = |name: &str| Some(Path::new("resources/textures").join(name).to_string_lossy().to_string());
+    assert_eq!(parse("painted_metal"), PbrMaterial {
+        base_color: glm::vec4(0.8, 0.1, 0.1, 0.5),
+        metallic: 0.9,
+        roughness: 0.2,
+        emissive: glm::vec3(0.5, 0.25, 0.0),
+        normal_scale: 0.5,
+        base_color_texture: texture("paint.png"),
+        metallic_texture: texture("metallic.png"),
+        roughness_texture: texture("roughness.png"),
+        emissive_texture: texture("glow.png"),
+        normal_texture: texture("normal.png"),
+    });
+}
+
+#[test]
+fn classic_material() {
+    let material = parse("plain");
+    assert_eq!(material.base_color, glm::vec4(0.5, 0.5, 0.5, 1.0));
+    assert_eq!(material.metallic, 0.0);
+    assert!((material.roughness - 0.125f32.sqrt()).abs() < 1e-6, "roughness {} from Ns 14", material.roughness);
+    assert_eq!(material.emissive, glm::vec3(0.0, 0.0, 0.0));
+    assert_eq!(material.normal_scale, 1.0);
+    assert_eq!(material.base_color_texture, None);
+    assert_eq!(material.normal_texture, None);
+}
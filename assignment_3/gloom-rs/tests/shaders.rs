@@ -0,0 +1,294 @@
+// Validates every shader under shaders/ without an OpenGL context, so shader errors show up in `cargo test` on CI.
+// The sources go through the same preprocessor as at runtime, and are then parsed and validated by naga's GLSL front end.
+// The uniforms set from the Rust code are checked against the uniforms declared by the programs they are set on.
+
+extern crate nalgebra_glm as glm;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use gloom_engine::{preprocessor, texture};
+
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+fn stage(path: &str) -> Option<naga::ShaderStage> {
+    match path.rsplit('.').next()? {
+        "vert" => Some(naga::ShaderStage::Vertex),
+        "frag" => Some(naga::ShaderStage::Fragment),
+        "comp" => Some(naga::ShaderStage::Compute),
+        _ => None,
+    }
+}
+
+// naga only accepts Vulkan flavoured GLSL, which needs version 440 or later, an explicit binding on every uniform, and
+// separate textures and samplers. None of it changes what is being checked, so the preprocessed source is adjusted
+// rather than the shaders themselves. A combined sampler like
+//     uniform sampler2D name;
+// becomes a texture2D called name and a sampler called name_sampler, and every use of name is replaced by
+// sampler2D(name, name_sampler). Bool uniforms aren't allowed either, so they become ints compared to 0 where used,
+// and gl_VertexID is called gl_VertexIndex.
+fn to_naga_glsl(source: &str) -> String {
+    let mut binding = 16;
+    let mut replacements: Vec<(String, String)> = vec![];
+    let lines = source.lines().map(|line| {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#version") {
+            return "#version 450 core".to_string();
+        }
+        if let Some(name) = trimmed.strip_prefix("uniform bool ").map(|rest| rest.trim_end_matches(';').trim()) {
+            replacements.push((name.to_string(), format!("({} != 0)", name)));
+            binding += 1;
+            return format!("layout(binding = {}) uniform int {};", binding, name);
+        }
+        if let Some((sampler_type, name)) = combined_sampler(trimmed) {
+            let (texture_type, separate_sampler) = split_sampler_type(&sampler_type);
+            replacements.push((name.clone(), format!("{}({}, {}_sampler)", sampler_type, name, name)));
+            binding += 2;
+            return format!("layout(binding = {}) uniform {} {}; layout(binding = {}) uniform {} {}_sampler;",
+                           binding - 1, texture_type, name, binding, separate_sampler, name);
+        }
+        let line = replace_identifier(line, "gl_VertexID", "gl_VertexIndex");
+        let line = replacements.iter().fold(line, |line, (name, replacement)| replace_identifier(&line, name, replacement));
+        if trimmed.starts_with("uniform ") {
+            binding += 1;
+            format!("layout(binding = {}) {}", binding, line.trim_start())
+        } else {
+            line
+        }
+    }).collect::<Vec<String>>();
+    lines.join("\n")
+}
+
+// The type and name of a combined sampler declaration, with or without a layout qualifier
+fn combined_sampler(line: &str) -> Option<(String, String)> {
+    let declaration = &line[line.find("uniform ")? + "uniform ".len()..];
+    let mut words = declaration.trim_end_matches(';').split_whitespace();
+    let (sampler_type, name) = (words.next()?, words.next()?);
+    let is_sampler = ["sampler", "isampler", "usampler"].iter()
+        .any(|prefix| sampler_type.starts_with(prefix) && sampler_type.len() > prefix.len());
+    if is_sampler && words.next().is_none() {
+        Some((sampler_type.to_string(), name.to_string()))
+    } else {
+        None
+    }
+}
+
+// The texture and sampler types a combined sampler type is made of, e.g. texture2DArray and samplerShadow for sampler2DArrayShadow
+fn split_sampler_type(sampler_type: &str) -> (String, &'static str) {
+    let texture_type = sampler_type.replacen("sampler", "texture", 1);
+    match texture_type.strip_suffix("Shadow") {
+        Some(texture_type) => (texture_type.to_string(), "samplerShadow"),
+        None => (texture_type, "sampler"),
+    }
+}
+
+// Replace whole word occurrences of name in line
+fn replace_identifier(line: &str, name: &str, replacement: &str) -> String {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(name) {
+        let end = start + name.len();
+        let before = rest[..start].chars().next_back();
+        let after = rest[end..].chars().next();
+        result += &rest[..start];
+        if before.map_or(true, |c| !is_identifier(c)) && after.map_or(true, |c| !is_identifier(c)) {
+            result += replacement;
+        } else {
+            result += name;
+        }
+        rest = &rest[end..];
+    }
+    result + rest
+}
+
+// naga nests its validation errors, and only the innermost one says what is actually wrong
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(inner) = source {
+        message += &format!(": {}", inner);
+        source = inner.source();
+    }
+    message
+}
+
+// Names of the loose uniforms and uniform block members of a module
+fn uniform_names(module: &naga::Module) -> Vec<String> {
+    let mut names = vec![];
+    for (_, variable) in module.global_variables.iter() {
+        if variable.space != naga::AddressSpace::Uniform && variable.space != naga::AddressSpace::Handle {
+            continue;
+        }
+        match (&variable.name, &module.types[variable.ty].inner) {
+            (None, naga::TypeInner::Struct { members, .. }) => {
+                names.extend(members.iter().filter_map(|m| m.name.clone()));
+            }
+            (Some(name), _) => names.push(name.clone()),
+            _ => {}
+        }
+    }
+    names
+}
+
+// Every shader stage under shaders/, with the uniforms each declares
+fn parse_shaders() -> Vec<(String, Vec<String>)> {
+    let mut paths = fs::read_dir("shaders").expect("Failed to read shaders/")
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().to_string_lossy().to_string())
+        .filter(|p| stage(p).is_some())
+        .collect::<Vec<String>>();
+    paths.sort();
+
+    let mut shaders = vec![];
+    let mut failures = vec![];
+    for path in paths {
+        let preprocessed = match preprocessor::preprocess_file(&path, &[]) {
+            Ok(preprocessed) => preprocessed,
+            Err(e) => {
+                failures.push(e.to_string());
+                continue;
+            }
+        };
+
+        let source = to_naga_glsl(&preprocessed.source);
+        let module = match Frontend::default().parse(&Options::from(stage(&path).unwrap()), &source) {
+            Ok(module) => module,
+            Err(errors) => {
+                for error in errors {
+                    let line = source[..error.meta.to_range().map_or(0, |r| r.start)].lines().count().max(1);
+                    let (file, line) = preprocessed.origin(line).unwrap_or((&path, line));
+                    failures.push(format!("{}:{}: {}", file, line, error.kind));
+                }
+                continue;
+            }
+        };
+
+        if let Err(error) = Validator::new(ValidationFlags::all(), Capabilities::all()).validate(&module) {
+            failures.push(format!("{}: {}", path, error_chain(&error.into_inner())));
+            continue;
+        }
+        shaders.push((path, uniform_names(&module)));
+    }
+
+    assert!(failures.is_empty(), "Invalid shaders:\n{}", failures.join("\n"));
+    shaders
+}
+
+// The texture slot constants of gloom_engine::texture by name, as the Rust code refers to them
+const TEXTURE_SLOTS: &[(&str, &str)] = &[
+    ("DIFFUSE", texture::DIFFUSE),
+    ("NORMAL", texture::NORMAL),
+    ("SPECULAR", texture::SPECULAR),
+    ("EMISSIVE", texture::EMISSIVE),
+    ("BASE_COLOR", texture::BASE_COLOR),
+    ("METALLIC", texture::METALLIC),
+    ("ROUGHNESS", texture::ROUGHNESS),
+];
+
+const SIMPLE: &[&str] = &["simple.vert", "simple.frag"];
+const PBR: &[&str] = &["simple.vert", "pbr.frag"];
+const PARTICLES: &[&str] = &["particles.vert", "particles.frag"];
+const PARTICLE_SIMULATION: &[&str] = &["particles.comp"];
+const SHADOW: &[&str] = &["shadow.vert", "shadow.frag"];
+const SKYBOX: &[&str] = &["skybox.vert", "skybox.frag"];
+
+// The programs that the functions setting uniforms set them on, as the stages under shaders/ they are linked from.
+// A uniform has to be declared by every program its function may be drawing with. Functions that set uniforms and
+// aren't listed here fail the test, so they have to be added when they are written.
+const PROGRAMS: &[(&str, &str, &[&[&str]])] = &[
+    ("main.rs", "draw_scene", &[SIMPLE, PBR]),
+    ("main.rs", "main", &[SIMPLE]),
+    ("particle_system.rs", "tick", &[PARTICLE_SIMULATION]),
+    ("particle_system.rs", "render", &[PARTICLES]),
+    ("pbr.rs", "to_material", &[PBR]),
+    ("shadow.rs", "render", &[SHADOW]),
+    ("shadow.rs", "draw_depth", &[SHADOW]),
+    ("skybox.rs", "draw", &[SKYBOX]),
+];
+
+// The name of the function that the code at position is in, which is the last one declared before it
+fn enclosing_function(source: &str, position: usize) -> String {
+    let declaration = &source[source[..position].rfind("fn ").map_or(0, |start| start + 3)..];
+    declaration[..declaration.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(0)].to_string()
+}
+
+// The uniform names set in src/, with the file and function setting them: string literals passed to set_uniform,
+// set_uniform_mat4 and Material::set, and the texture slots that are referred to
+fn uniforms_set_in_rust() -> Vec<(String, String, String)> {
+    let mut uniforms = vec![];
+    for entry in fs::read_dir("src").expect("Failed to read src/").filter_map(|e| e.ok()) {
+        let file = entry.file_name().to_string_lossy().to_string();
+        let source = fs::read_to_string(entry.path()).unwrap();
+        let mut add = |start: usize, name: &str| {
+            uniforms.push((file.clone(), enclosing_function(&source, start), name.to_string()));
+        };
+        for call in &["set_uniform(\"", "set_uniform_mat4(\"", ".set(\""] {
+            for (start, _) in source.match_indices(call) {
+                let name = &source[start + call.len()..];
+                add(start, &name[..name.find('"').unwrap()]);
+            }
+        }
+        for (constant, name) in TEXTURE_SLOTS {
+            for (start, _) in source.match_indices(&format!("texture::{}", constant)) {
+                add(start, name);
+            }
+        }
+    }
+    uniforms
+}
+
+#[test]
+fn shaders_are_valid() {
+    let shaders = parse_shaders();
+    assert!(!shaders.is_empty(), "No shaders found in shaders/");
+}
+
+#[test]
+fn uniforms_set_from_rust_exist() {
+    let declared = parse_shaders().into_iter()
+        .map(|(path, uniforms)| (path.trim_start_matches("shaders/").to_string(), uniforms))
+        .collect::<HashMap<String, Vec<String>>>();
+
+    let mut missing = vec![];
+    for (file, function, name) in uniforms_set_in_rust() {
+        let programs = match PROGRAMS.iter().find(|&&(f, n, _)| f == file && n == function) {
+            Some((_, _, programs)) => programs,
+            None => {
+                missing.push(format!("{} sets {} in {}, which has no programs in PROGRAMS", file, name, function));
+                continue;
+            }
+        };
+        for stages in programs.iter() {
+            let uniforms = stages.iter()
+                .flat_map(|stage| declared.get(*stage).unwrap_or_else(|| panic!("No shader {} in shaders/", stage)))
+                .collect::<HashSet<&String>>();
+            if !uniforms.contains(&name.trim_end_matches("[0]").to_string()) {
+                missing.push(format!("{} sets {} in {}, but {} doesn't declare it", file, name, function, stages.join(" + ")));
+            }
+        }
+    }
+    assert!(missing.is_empty(), "Uniforms not declared by the programs they are set on:\n{}", missing.join("\n"));
+}
+
+#[test]
+fn naga_rewrite() {
+    let source = to_naga_glsl("#version 430 core\n\
+                               uniform sampler2D albedo;\n\
+                               layout(binding = 3) uniform sampler2DArrayShadow shadow_map;\n\
+                               uniform bool use_fog;\n\
+                               uniform float time;\n\
+                               void main() {\n\
+                               vec4 color = texture(albedo, uv) * albedo_scale;\n\
+                               if (use_fog) { color *= gl_VertexID; }\n\
+                               }");
+    let lines = source.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[0], "#version 450 core");
+    assert_eq!(lines[1], "layout(binding = 17) uniform texture2D albedo; layout(binding = 18) uniform sampler albedo_sampler;");
+    assert_eq!(lines[2], "layout(binding = 19) uniform texture2DArray shadow_map; layout(binding = 20) uniform samplerShadow shadow_map_sampler;");
+    assert_eq!(lines[3], "layout(binding = 21) uniform int use_fog;");
+    assert_eq!(lines[4], "layout(binding = 22) uniform float time;");
+    assert_eq!(lines[6], "vec4 color = texture(sampler2D(albedo, albedo_sampler), uv) * albedo_scale;");
+    assert_eq!(lines[7], "if ((use_fog != 0)) { color *= gl_VertexIndex; }");
+}
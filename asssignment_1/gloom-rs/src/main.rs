@@ -1,3 +1,5 @@
+#[macro_use]
+extern crate gloom_engine;
 mod my_format;
 
 extern crate nalgebra_glm as glm;
@@ -11,9 +13,9 @@ use std::{
 };
 use std::thread;
 use std::sync::{Mutex, Arc, RwLock};
+use std::cell::RefCell;
+use std::rc::Rc;
 
-mod shader;
-mod util;
 mod wavefront;
 mod model;
 
@@ -22,7 +24,8 @@ use glutin::event::{Event, WindowEvent, KeyboardInput, ElementState::{Pressed, R
 use glutin::event_loop::ControlFlow;
 use glm::length;
 use std::ffi::CString;
-use crate::model::Model;
+use gloom_engine::{loader, shader, util};
+use gloom_engine::material::Material;
 
 const SCREEN_W: u32 = 800;
 const SCREEN_H: u32 = 600;
@@ -44,34 +47,6 @@ fn offset<T>(n: u32) -> *const c_void {
 }
 
 
-unsafe fn setup_vao(vertices: &Vec<f32>, indices: &Vec<u32>) -> u32 {
-    let mut vao = 0;
-    let mut vbo = 0;
-    let mut ibo = 0;
-
-    // Generate VAO
-    gl::GenVertexArrays(1, &mut vao);
-    gl::BindVertexArray(vao);
-
-    //Generate vertex and index buffers
-    gl::GenBuffers(1, &mut vbo);
-    gl::GenBuffers(1, &mut ibo);
-
-    //Fill vertex buffer
-    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-    gl::BufferData(gl::ARRAY_BUFFER, byte_size_of_array(vertices), util::pointer_to_array(vertices), gl::STATIC_DRAW);
-
-    // Fill index buffer
-    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
-    gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, byte_size_of_array(indices), util::pointer_to_array(indices), gl::STATIC_DRAW);
-
-    // Configure vertex attribute layout
-    gl::EnableVertexAttribArray(0);
-    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, size_of::<f32>() * 3, ptr::null());
-    gl::BindVertexArray(0);
-    return vao;
-}
-
 fn main() {
     // Set up the necessary objects to deal with windows and event handling
     let el = glutin::event_loop::EventLoop::new();
@@ -134,55 +109,48 @@ fn main() {
                 **/
 
 
-        // == // Set up your VAO here
-        //let vao = unsafe { setup_vao(&vertices, &indices) };
-
-        // Load meshes and textures.
-        let mut ball: Model;
-        let mut torus: Model;
-        let mut cube: Model;
-
-        // Load models
-        unsafe {
-            ball = my_format::load("resources/ball.myf");
-            ball.attach_texture("resources/ball.png");
-            ball.init();
-
-            cube = wavefront::load("resources/cube.obj");
-            cube.attach_texture("resources/cube.png");
-            cube.init();
-
-            torus = my_format::load("resources/torus.myf");
-            torus.attach_texture("resources/torus.png");
-            torus.init();
-        }
-
-        //Move models to right location
-        torus.translate(glm::Vec3::new(-2.0, 0.0, 0.0));
-        cube.translate(glm::Vec3::new(2.0, 0.0, 0.0));
-
         // Basic usage of shader helper
-        // The code below returns a shader object, which contains the field .program_id
+        // The code below returns a shader object, which contains the method .program_id()
         // The snippet is not enough to do the assignment, and will need to be modified (outside of just using the correct path)
         let shader_program: shader::Shader;
         unsafe {
-            shader_program = shader::ShaderBuilder::new().attach_file("shaders/simple.vert").attach_file("shaders/simple.frag").link();
-
-            // Set the view and projection matrices
-            shader_program.activate();
-
-            // set the view matrix
-            shader_program.set_uniform_mat4("view_matrix",
-                                            &glm::look_at(
-                                                &glm::Vec3::new(0.0, 4.0, 4.0),
-                                                &glm::Vec3::new(0.0, 0.0, 0.0),
-                                                &glm::Vec3::new(0.0, 1.0, 0.0)));
+            shader_program = shader::ShaderBuilder::new()
+                .attach_file("shaders/simple.vert")
+                .and_then(|builder| builder.attach_file("shaders/simple.frag"))
+                .and_then(|builder| builder.link())
+                .unwrap_or_else(|e| panic!("{}", e));
+        };
 
-            // set the projection matrix
-            shader_program.set_uniform_mat4("projection_matrix",
-                                            &glm::perspective(800 as f32 / SCREEN_H as f32, (3.14 / 180.0) * 60.0, 0.1, 100.0));
+        // All models are drawn with the same material, which holds the view and projection matrices
+        let mut default_material = Material::new(Rc::new(RefCell::new(shader_program)));
+
+        // set the view matrix
+        default_material.set("view_matrix",
+                              glm::look_at(
+                                  &glm::Vec3::new(0.0, 4.0, 4.0),
+                                  &glm::Vec3::new(0.0, 0.0, 0.0),
+                                  &glm::Vec3::new(0.0, 1.0, 0.0)));
+
+        // set the projection matrix
+        default_material.set("projection_matrix",
+                              glm::perspective(800 as f32 / SCREEN_H as f32, (3.14 / 180.0) * 60.0, 0.1, 100.0));
+
+        // Load meshes and textures on worker threads. The models stay empty placeholders until their upload has run,
+        // and are reloaded when their files in resources/ change.
+        let mut asset_loader = loader::AssetLoader::new(3);
+        asset_loader.watch("resources", &["obj", "myf", "png"]);
+        let (ball, cube, torus) = unsafe {
+            (
+                model::load(&mut asset_loader, "resources/ball.myf", "resources/ball.png", default_material.clone()),
+                model::load(&mut asset_loader, "resources/cube.obj", "resources/cube.png", default_material.clone()),
+                model::load(&mut asset_loader, "resources/torus.myf", "resources/torus.png", default_material.clone()),
+            )
         };
 
+        //Move models to right location
+        torus.borrow_mut().translate(glm::Vec3::new(-2.0, 0.0, 0.0));
+        cube.borrow_mut().translate(glm::Vec3::new(2.0, 0.0, 0.0));
+
 
         // Used to demonstrate keyboard handling -- feel free to remove
         let mut _arbitrary_number = 0.0;
@@ -196,6 +164,9 @@ fn main() {
             let delta_time = now.duration_since(last_frame_time).as_secs_f32();
             last_frame_time = now;
 
+            // Upload models that finished loading since last frame, and reload changed ones
+            asset_loader.process_uploads();
+
             // Handle keyboard input
             if let Ok(keys) = pressed_keys.lock() {
                 for key in keys.iter() {
@@ -213,18 +184,19 @@ fn main() {
             }
 
             // Rotate everything once per frame
-            ball.rotate(glm::Vec3::new(0.0, 1.0, 0.0), 0.01);
-            torus.rotate(glm::Vec3::new(0.0, 0.0, 1.0), -0.01);
-            cube.rotate(glm::Vec3::new(1.0, 0.0, 1.0), 0.01);
+            ball.borrow_mut().rotate(glm::Vec3::new(0.0, 1.0, 0.0), 0.01);
+            torus.borrow_mut().rotate(glm::Vec3::new(0.0, 0.0, 1.0), -0.01);
+            cube.borrow_mut().rotate(glm::Vec3::new(1.0, 0.0, 1.0), 0.01);
 
             unsafe {
                 gl::ClearColor((elapsed / 10.0) % 1.0, (elapsed / 7.0) % 1.0, (elapsed /8.0) % 1.0, 1.0);
-                gl::Clear(gl::COLOR_BUFFER_BIT);
+                // The material enables depth testing
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
                 // Render models
-                ball.render(&shader_program);
-                torus.render(&shader_program);
-                cube.render(&shader_program);
+                ball.borrow().render();
+                torus.borrow().render();
+                cube.borrow().render();
             }
 
             context.swap_buffers().unwrap();
@@ -1,7 +1,13 @@
-use crate::util;
-use std::ptr;
-use crate::shader::Shader;
-
+use std::cell::RefCell;
+use std::{mem, ptr};
+use std::rc::Rc;
+use gloom_engine::gl_object::{Handle, Pod, Texture, VertexArray};
+use gloom_engine::loader::AssetLoader;
+use gloom_engine::texture::{self, TextureOptions, TextureSet};
+use gloom_engine::material::Material;
+use crate::{my_format, wavefront};
+
+#[repr(C)]
 #[derive(Copy, Clone, PartialEq)]
 pub struct Vertex {
     pub position: glm::Vec3,
@@ -9,23 +15,37 @@ pub struct Vertex {
     pub tex_coord: glm::Vec2,
 }
 
+unsafe impl Pod for Vertex {}
+
+vertex_layout!(Vertex {
+    position => "position" @ 0,
+    normal => "normal" @ 1,
+    tex_coord => "tex_coord" @ 2,
+});
+
+pub type ModelHandle = Rc<RefCell<Model>>;
 
+/**
+    Clones share the VAO, textures and shader of the model, which are deleted once the last clone is dropped.
+*/
 #[derive(Clone)]
 pub struct Model {
     vertices: Vec<Vertex>,
     indices: Vec<u32>,
-    vao: u32,
+    vao: Option<Handle<VertexArray>>,
     model_matrix: glm::Mat4,
-    texture: u32,
+    textures: TextureSet,
+    material: Material,
 }
 
 impl Model {
-    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Model {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, material: Material) -> Model {
         return Model {
             vertices,
             indices,
-            vao: 0,
-            texture: 0,
+            vao: None,
+            textures: TextureSet::new(),
+            material,
             model_matrix: glm::Mat4::new(
                 1.0, 0.0, 0.0, 0.0,
                 0.0, 1.0, 0.0, 0.0,
@@ -34,77 +54,60 @@ impl Model {
         };
     }
 
+    /**
+        An empty model to stand in for one that is still loading. Renders nothing.
+    */
+    pub fn placeholder(material: Material) -> Model {
+        Model::new(vec![], vec![], material)
+    }
+
+    /**
+        Replace the mesh and textures with the ones of another model, keeping the current transformation and material.
+    */
+    pub fn replace_with(&mut self, mut other: Model) {
+        other.model_matrix = self.model_matrix;
+        mem::swap(&mut other.material, &mut self.material);
+        *self = other;
+    }
+
     /*
         Create the buffers and insert data. And create the vao and vertex layout.
     */
     pub unsafe fn init(&mut self) {
-        let mut vbo = 0;
-        let mut ibo = 0;
-
-        // Generate VAO
-        gl::GenVertexArrays(1, &mut self.vao);
-        gl::BindVertexArray(self.vao);
-
-        //Generate vertex and index buffers
-        gl::GenBuffers(1, &mut vbo);
-        gl::GenBuffers(1, &mut ibo);
-
-        let buffer_data = self.vertices.iter().flat_map(
-            |vertex| vec![
-                vertex.position.x, vertex.position.y, vertex.position.z,
-                vertex.normal.x, vertex.normal.y, vertex.normal.z,
-                vertex.tex_coord.x, vertex.tex_coord.y
-            ]).collect::<Vec<f32>>();
-
-        //Fill vertex buffer
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(&buffer_data), util::pointer_to_array(&buffer_data), gl::STATIC_DRAW);
-
-        // Fill index buffer
-        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
-        gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, util::byte_size_of_array(&self.indices), util::pointer_to_array(&self.indices), gl::STATIC_DRAW);
-
-        // Configure vertex attribute layout
-        // Position vec3
-        gl::EnableVertexAttribArray(0);
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 8, ptr::null());
-
-        // normal vec3
-        gl::EnableVertexAttribArray(1);
-        gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 8, (3 * util::size_of::<f32>()) as *const gl::types::GLvoid);
-
-        // tex coord vec2
-        gl::EnableVertexAttribArray(2);
-        gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 8, (6 * util::size_of::<f32>()) as *const gl::types::GLvoid);
-        gl::BindVertexArray(0);
+        self.vao = Some(Handle::new(VertexArray::from_vertices(&self.vertices, &self.indices)));
     }
 
-    // Render the mesh with the given shader. Assumes the shader has a model_matrix uniform
-    pub unsafe fn render(&self, shader: &Shader) {
-        gl::BindVertexArray(self.vao);
+    // Render the mesh with its material. Assumes the material's shader has a model_matrix uniform, and a sampler for
+    // each texture slot. Textures of the model replace material textures in the same slot.
+    pub unsafe fn render(&self) {
+        let vao = match &self.vao {
+            Some(vao) => vao,
+            None => return,
+        };
+
+        vao.bind();
 
-        shader.activate();
+        let (shader, texture_units) = self.material.apply();
         // Update the model matrix
         shader.set_uniform_mat4("model_matrix", &self.model_matrix);
 
-        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        self.textures.bind(&shader, texture_units);
 
         gl::DrawElements(gl::TRIANGLES, self.indices.len() as i32, gl::UNSIGNED_INT, ptr::null());
     }
 
     /**
-        Load a texture from a file and attach it tp this model.
+        Put an already loaded texture in the given slot of this model.
     */
-    pub unsafe fn attach_texture(&mut self, path: &str) {
-        let img = image::open(path).expect(&format!("Failed to load image {}", &path)).to_rgb();
-        gl::GenTextures(1, &mut self.texture);
-        gl::BindTexture(gl::TEXTURE_2D, self.texture);
-
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+    pub fn set_texture(&mut self, slot: &str, texture: Handle<Texture>) {
+        self.textures.set(slot, texture);
+    }
 
-        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGB as i32, img.width() as i32, img.height() as i32, 0, gl::RGB, gl::UNSIGNED_BYTE, util::pointer_to_array(&img.into_raw()));
-        gl::GenerateMipmap(gl::TEXTURE_2D);
+    /**
+        Draw the model with another material, e.g. one with a different shader or render state.
+    */
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
     }
 
     /**
@@ -122,4 +125,30 @@ impl Model {
         self.model_matrix = glm::translate(&self.model_matrix, &translation);
         self
     }
-}
\ No newline at end of file
+}
+
+/**
+    Load a model and its diffuse texture with the default texture options, drawn with the given material. The returned
+    handle holds an empty placeholder model until the upload is done, and the model is swapped out behind the handle when
+    it is reloaded.
+*/
+pub unsafe fn load(loader: &mut AssetLoader, path: &str, texture_path: &str, material: Material) -> ModelHandle {
+    let handle = Rc::new(RefCell::new(Model::placeholder(material.clone())));
+    let target = Rc::downgrade(&handle);
+    let model_path = path.to_string();
+    let texture = loader.load_texture(texture_path, TextureOptions::default());
+
+    loader.load_watched(&[path], move || {
+        if model_path.ends_with(".obj") { wavefront::load(&model_path) } else { my_format::load(&model_path) }
+    }, move |(vertices, indices): (Vec<Vertex>, Vec<u32>)| {
+        if let Some(target) = target.upgrade() {
+            // The material is swapped for the one the model has now
+            let mut model = Model::new(vertices, indices, material.clone());
+            model.set_texture(texture::DIFFUSE, Handle::clone(&texture));
+            unsafe { model.init() };
+            target.borrow_mut().replace_with(model);
+        }
+    });
+
+    handle
+}
@@ -1,9 +1,9 @@
 use std::path::Path;
-use crate::model::{Model, Vertex};
+use crate::model::Vertex;
 
 extern crate nalgebra_glm as glm;
 
-pub fn load(path: &str) -> Model {
+pub fn load(path: &str) -> (Vec<Vertex>, Vec<u32>) {
     let src = std::fs::read_to_string(Path::new(path))
         .expect(&format!("Failed to read .myf file. {}", path));
 
@@ -26,7 +26,7 @@ pub fn load(path: &str) -> Model {
         vertices.push(vertex);
     }
 
-    Model::new(vertices, index_line.strip_prefix(" ")
+    (vertices, index_line.strip_prefix(" ")
         .unwrap().split(" ")
         .map(|s| s.parse::<u32>().expect("Failed to parse u32"))
         .collect::<Vec<u32>>())
@@ -3,15 +3,15 @@ use std::{ffi::CString, path::Path, ptr, str};
 
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
-use crate::model::{Model, Vertex};
+use crate::model::Vertex;
 
 extern crate nalgebra_glm as glm;
 
 /**
-    Read a Wavefront file and output a Mesh. The file has to have posititions, normals and tex_coords.
+    Read a Wavefront file and output the vertices and indices of its mesh. The file has to have posititions, normals and tex_coords.
     It will also need triangulated faces.
 */
-pub fn load(path: &str) -> Model {
+pub fn load(path: &str) -> (Vec<Vertex>, Vec<u32>) {
     let src = std::fs::read_to_string(Path::new(path))
         .expect(&format!("Failed to read .obj file. {}", path));
 
@@ -35,7 +35,7 @@ pub fn load(path: &str) -> Model {
         }
     }
 
-    Model::new(vertices, indices)
+    (vertices, indices)
 }
 
 /**
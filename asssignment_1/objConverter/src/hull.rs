@@ -0,0 +1,360 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+
+use crate::mesh::Mesh;
+
+extern crate nalgebra_glm as glm;
+
+/*
+    A closed convex triangle mesh. Triangles are wound counter clockwise when seen from the outside.
+*/
+#[derive(Clone)]
+pub struct ConvexHull {
+    pub points: Vec<glm::Vec3>,
+    pub indices: Vec<u32>,
+}
+
+struct Face {
+    vertices: [usize; 3],
+    normal: glm::Vec3,
+    offset: f32,
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+impl Face {
+    fn new(points: &[glm::Vec3], vertices: [usize; 3]) -> Face {
+        let a = points[vertices[0]];
+        let normal = glm::normalize(&glm::cross(&(points[vertices[1]] - a), &(points[vertices[2]] - a)));
+        Face {
+            vertices,
+            normal,
+            offset: glm::dot(&normal, &a),
+            outside: vec![],
+            alive: true,
+        }
+    }
+
+    fn distance(&self, point: &glm::Vec3) -> f32 {
+        glm::dot(&self.normal, point) - self.offset
+    }
+
+    fn edges(&self) -> [(usize, usize); 3] {
+        let [a, b, c] = self.vertices;
+        [(a, b), (b, c), (c, a)]
+    }
+}
+
+impl ConvexHull {
+    /**
+        Compute the convex hull of a point cloud with the quickhull algorithm.
+        Returns None if the points are degenerate, i.e. all lie in a plane.
+    */
+    pub fn from_points(points: &[glm::Vec3]) -> Option<ConvexHull> {
+        if points.len() < 4 {
+            return None;
+        }
+
+        let (min, max) = bounds(points);
+        let epsilon = 1e-5 * glm::length(&(max - min)).max(1.0);
+
+        let simplex = initial_simplex(points, epsilon)?;
+        let mut faces: Vec<Face> = vec![];
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+
+        // Build the tetrahedron with every face pointing away from the vertex opposite to it
+        for &(face, apex) in &[([0, 1, 2], 3), ([0, 1, 3], 2), ([0, 2, 3], 1), ([1, 2, 3], 0)] {
+            let [a, b, c]: [usize; 3] = face;
+            let mut vertices = [simplex[a], simplex[b], simplex[c]];
+            if Face::new(points, vertices).distance(&points[simplex[apex]]) > 0.0 {
+                vertices.swap(1, 2);
+            }
+            add_face(&mut faces, &mut edges, Face::new(points, vertices));
+        }
+
+        let candidates = (0..points.len()).filter(|i| !simplex.contains(i)).collect::<Vec<usize>>();
+        assign_points(points, &mut faces, &[0, 1, 2, 3], &candidates, epsilon);
+
+        while let Some(face_index) = faces.iter().position(|f| f.alive && !f.outside.is_empty()) {
+            // The point furthest away from the face is guaranteed to be on the hull
+            let face = &faces[face_index];
+            let eye = *face.outside.iter()
+                .max_by(|&&a, &&b| face.distance(&points[a]).partial_cmp(&face.distance(&points[b])).unwrap())
+                .unwrap();
+
+            // Flood fill the faces visible from the eye point and find the horizon around them
+            let mut visible = vec![face_index];
+            let mut visited = HashSet::new();
+            visited.insert(face_index);
+            let mut horizon = vec![];
+            let mut stack = vec![face_index];
+            while let Some(current) = stack.pop() {
+                for &(a, b) in &faces[current].edges() {
+                    let neighbour = edges[&(b, a)];
+                    if visited.contains(&neighbour) {
+                        continue;
+                    }
+                    if faces[neighbour].distance(&points[eye]) > epsilon {
+                        visited.insert(neighbour);
+                        visible.push(neighbour);
+                        stack.push(neighbour);
+                    } else {
+                        horizon.push((a, b));
+                    }
+                }
+            }
+
+            let mut orphans = vec![];
+            for &index in &visible {
+                faces[index].alive = false;
+                orphans.extend(faces[index].outside.drain(..).filter(|&p| p != eye));
+                for edge in &faces[index].edges() {
+                    edges.remove(edge);
+                }
+            }
+
+            let new_faces = horizon.iter()
+                .map(|&(a, b)| add_face(&mut faces, &mut edges, Face::new(points, [a, b, eye])))
+                .collect::<Vec<usize>>();
+            assign_points(points, &mut faces, &new_faces, &orphans, epsilon);
+        }
+
+        // Compact the points so that only the ones on the hull are kept
+        let mut remap = HashMap::new();
+        let mut hull = ConvexHull { points: vec![], indices: vec![] };
+        for face in faces.iter().filter(|f| f.alive) {
+            for &vertex in &face.vertices {
+                let index = *remap.entry(vertex).or_insert_with(|| {
+                    hull.points.push(points[vertex]);
+                    hull.points.len() as u32 - 1
+                });
+                hull.indices.push(index);
+            }
+        }
+
+        Some(hull)
+    }
+
+    /**
+        How deep the deepest of the given points lies inside the hull. Zero if all points are on its surface.
+    */
+    fn concavity(&self, points: &[glm::Vec3]) -> f32 {
+        let faces = self.indices.chunks(3)
+            .map(|t| Face::new(&self.points, [t[0] as usize, t[1] as usize, t[2] as usize]))
+            .collect::<Vec<Face>>();
+
+        points.iter()
+            .map(|p| faces.iter().map(|f| -f.distance(p)).fold(f32::INFINITY, f32::min))
+            .fold(0.0, f32::max)
+    }
+}
+
+/**
+    Approximate convex decomposition of a mesh. The triangles are recursively split in two along the longest axis
+    of their bounding box until the hull of each part is within max_concavity of the part itself,
+    or max_hulls parts have been created.
+*/
+pub fn decompose(mesh: &Mesh, max_hulls: usize, max_concavity: f32) -> Vec<ConvexHull> {
+    let triangles = mesh.indices.chunks(3)
+        .map(|t| [mesh.vertices[t[0] as usize].position, mesh.vertices[t[1] as usize].position, mesh.vertices[t[2] as usize].position])
+        .collect::<Vec<[glm::Vec3; 3]>>();
+
+    let mut done: Vec<ConvexHull> = vec![];
+    let mut parts: Vec<(Vec<[glm::Vec3; 3]>, ConvexHull)> = vec![];
+    if let Some(hull) = part_hull(&triangles) {
+        parts.push((triangles, hull));
+    }
+
+    while let Some((triangles, hull)) = parts.pop() {
+        let positions = triangles.iter().flat_map(|t| t.iter().cloned()).collect::<Vec<glm::Vec3>>();
+        if done.len() + parts.len() + 2 > max_hulls || hull.concavity(&positions) <= max_concavity {
+            done.push(hull);
+            continue;
+        }
+
+        // Split at the median of the triangle centers along the longest axis
+        let (min, max) = bounds(&positions);
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+        let mut centers = triangles.iter().map(|t| (t[0][axis] + t[1][axis] + t[2][axis]) / 3.0).collect::<Vec<f32>>();
+        centers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let split = centers[centers.len() / 2];
+
+        let (below, above): (Vec<[glm::Vec3; 3]>, Vec<[glm::Vec3; 3]>) = triangles.iter()
+            .partition(|t| (t[0][axis] + t[1][axis] + t[2][axis]) / 3.0 < split);
+
+        match (part_hull(&below), part_hull(&above)) {
+            (Some(below_hull), Some(above_hull)) => {
+                parts.push((below, below_hull));
+                parts.push((above, above_hull));
+            }
+            _ => done.push(hull),
+        }
+    }
+
+    done
+}
+
+/**
+    Write the hulls to a Wavefront file, one object per hull.
+*/
+pub fn write_obj(path: &str, hulls: &[ConvexHull]) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    let mut index_offset = 1;
+
+    for (i, hull) in hulls.iter().enumerate() {
+        writeln!(file, "o hull_{}", i)?;
+        for point in &hull.points {
+            writeln!(file, "v {} {} {}", point.x, point.y, point.z)?;
+        }
+        for triangle in hull.indices.chunks(3) {
+            writeln!(file, "f {} {} {}", triangle[0] + index_offset, triangle[1] + index_offset, triangle[2] + index_offset)?;
+        }
+        index_offset += hull.points.len() as u32;
+    }
+    Ok(())
+}
+
+fn part_hull(triangles: &[[glm::Vec3; 3]]) -> Option<ConvexHull> {
+    ConvexHull::from_points(&triangles.iter().flat_map(|t| t.iter().cloned()).collect::<Vec<glm::Vec3>>())
+}
+
+fn bounds(points: &[glm::Vec3]) -> (glm::Vec3, glm::Vec3) {
+    points.iter().fold(
+        (points[0], points[0]),
+        |(min, max), p| (glm::min2(&min, p), glm::max2(&max, p)),
+    )
+}
+
+/**
+    Find four points spanning a tetrahedron with as large a volume as practical.
+*/
+fn initial_simplex(points: &[glm::Vec3], epsilon: f32) -> Option<[usize; 4]> {
+    let furthest = |distance: &dyn Fn(&glm::Vec3) -> f32| {
+        (0..points.len())
+            .max_by(|&a, &b| distance(&points[a]).partial_cmp(&distance(&points[b])).unwrap())
+            .unwrap()
+    };
+
+    // The two extreme points along the axis with the largest spread
+    let mut extremes = vec![];
+    for axis in 0..3 {
+        extremes.push((furthest(&|p| -p[axis]), furthest(&|p| p[axis])));
+    }
+    let (a, b) = extremes.into_iter()
+        .max_by(|&(a0, b0), &(a1, b1)| {
+            glm::distance(&points[a0], &points[b0]).partial_cmp(&glm::distance(&points[a1], &points[b1])).unwrap()
+        })
+        .unwrap();
+
+    let direction = glm::normalize(&(points[b] - points[a]));
+    let line_distance = |p: &glm::Vec3| glm::length(&glm::cross(&direction, &(p - points[a])));
+    let c = furthest(&line_distance);
+    if glm::distance(&points[a], &points[b]) < epsilon || line_distance(&points[c]) < epsilon {
+        return None;
+    }
+
+    let normal = glm::normalize(&glm::cross(&(points[b] - points[a]), &(points[c] - points[a])));
+    let plane_distance = |p: &glm::Vec3| glm::dot(&normal, &(p - points[a])).abs();
+    let d = furthest(&plane_distance);
+    if plane_distance(&points[d]) < epsilon {
+        return None;
+    }
+
+    Some([a, b, c, d])
+}
+
+fn add_face(faces: &mut Vec<Face>, edges: &mut HashMap<(usize, usize), usize>, face: Face) -> usize {
+    let index = faces.len();
+    for &edge in &face.edges() {
+        edges.insert(edge, index);
+    }
+    faces.push(face);
+    index
+}
+
+/**
+    Give each point to the face it lies furthest above. Points below every face are inside the hull and dropped.
+*/
+fn assign_points(points: &[glm::Vec3], faces: &mut Vec<Face>, candidates: &[usize], point_indices: &[usize], epsilon: f32) {
+    for &point in point_indices {
+        let mut best = None;
+        let mut best_distance = epsilon;
+        for &face in candidates {
+            let distance = faces[face].distance(&points[point]);
+            if distance > best_distance {
+                best = Some(face);
+                best_distance = distance;
+            }
+        }
+        if let Some(face) = best {
+            faces[face].outside.push(point);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube_corners() -> Vec<glm::Vec3> {
+        (0..8).map(|i| glm::vec3((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32)).collect()
+    }
+
+    // Volume enclosed by the hull, which is only positive if every triangle is wound counter clockwise from outside
+    fn volume(hull: &ConvexHull) -> f32 {
+        hull.indices.chunks(3)
+            .map(|t| glm::dot(&hull.points[t[0] as usize], &glm::cross(&hull.points[t[1] as usize], &hull.points[t[2] as usize])) / 6.0)
+            .sum()
+    }
+
+    fn contains(hull: &ConvexHull, point: &glm::Vec3) -> bool {
+        hull.indices.chunks(3)
+            .map(|t| Face::new(&hull.points, [t[0] as usize, t[1] as usize, t[2] as usize]))
+            .all(|face| face.distance(point) <= 1e-5)
+    }
+
+    #[test]
+    fn tetrahedron() {
+        let points = [glm::vec3(0.0, 0.0, 0.0), glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, 1.0)];
+        let hull = ConvexHull::from_points(&points).unwrap();
+        assert_eq!(hull.points.len(), 4);
+        assert_eq!(hull.indices.len(), 4 * 3);
+        assert!((volume(&hull) - 1.0 / 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn cube_with_points_inside() {
+        let mut points = cube_corners();
+        points.extend((1..4).map(|i| glm::vec3(0.25 * i as f32, 0.5, 0.3)));
+        points.push(glm::vec3(0.9, 0.1, 0.9));
+        let hull = ConvexHull::from_points(&points).unwrap();
+
+        // Only the corners are on the hull, and each side of the cube is split in two triangles
+        assert_eq!(hull.points.len(), 8);
+        assert!(cube_corners().iter().all(|corner| hull.points.contains(corner)));
+        assert_eq!(hull.indices.len(), 12 * 3);
+        assert!((volume(&hull) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn points_inside_and_outside() {
+        let hull = ConvexHull::from_points(&cube_corners()).unwrap();
+        assert!(contains(&hull, &glm::vec3(0.5, 0.5, 0.5)));
+        assert!(contains(&hull, &glm::vec3(0.01, 0.99, 0.5)));
+        assert!(contains(&hull, &glm::vec3(1.0, 1.0, 1.0)));
+        assert!(contains(&hull, &glm::vec3(0.5, 0.0, 0.5)));
+        assert!(!contains(&hull, &glm::vec3(1.1, 0.5, 0.5)));
+        assert!(!contains(&hull, &glm::vec3(0.5, -0.01, 0.5)));
+        assert!(!contains(&hull, &glm::vec3(2.0, 2.0, 2.0)));
+        assert_eq!(hull.concavity(&[glm::vec3(0.5, 0.5, 0.5)]), 0.5);
+    }
+
+    #[test]
+    fn flat_points_have_no_hull() {
+        let points = (0..10).map(|i| glm::vec3(i as f32, (i * i) as f32, 0.0)).collect::<Vec<glm::Vec3>>();
+        assert!(ConvexHull::from_points(&points).is_none());
+        assert!(ConvexHull::from_points(&cube_corners()[..3]).is_none());
+    }
+}
@@ -1,16 +1,27 @@
 mod my_format;
 mod wavefront;
 mod mesh;
+mod hull;
 
 use std::env;
 
 /*
    Helping program to convert .obj files to .myf files. The format is a lot faster for loading indexed vertices.
+   Optionally also writes a collision mesh made of convex hulls to a separate .obj file:
+       objConverter model.obj model.myf [model.collision.obj [max_hulls]]
 */
 fn main(){
     let args: Vec<String> = env::args().collect();
     println!("{:?}", args);
     let mesh = unsafe { wavefront::load(&args[1]) };
+
+    if let Some(collision_path) = args.get(3) {
+        let max_hulls = args.get(4).map(|s| s.parse::<usize>().expect("Failed to parse max hulls")).unwrap_or(8);
+        let hulls = hull::decompose(&mesh, max_hulls, 0.05 * mesh.bounding_radius());
+        println!("Generated {} convex hulls", hulls.len());
+        hull::write_obj(collision_path, &hulls).expect("Failed to write collision mesh");
+    }
+
     my_format::write(&args[2], mesh);
 
-}
\ No newline at end of file
+}
@@ -23,4 +23,11 @@ impl Mesh {
             indices
         }
     }
+
+    /*
+        Largest distance from the origin to any vertex.
+    */
+    pub fn bounding_radius(&self) -> f32 {
+        self.vertices.iter().map(|v| glm::length(&v.position)).fold(0.0, f32::max)
+    }
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 #version 430 core
 
-in vec3 position;
-in vec4 color;
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
 
 out vec4 vertexColor;
 
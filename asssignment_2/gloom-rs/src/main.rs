@@ -1,14 +1,15 @@
+#[macro_use]
+extern crate gloom_engine;
 mod particle_system;
 
 extern crate nalgebra_glm as glm;
 
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::{mem, os::raw::c_void, ptr};
 
-mod shader;
-mod util;
-
 use glutin::event::{
     DeviceEvent,
     ElementState::{Pressed, Released},
@@ -18,6 +19,9 @@ use glutin::event::{
 };
 use glutin::event_loop::ControlFlow;
 use crate::particle_system::ParticleSystem;
+use gloom_engine::{shader, util};
+use gloom_engine::gl_object::{Pod, VertexArray};
+use gloom_engine::material::ShaderHandle;
 
 const SCREEN_W: u32 = 800;
 const SCREEN_H: u32 = 600;
@@ -25,36 +29,20 @@ const SCREEN_H: u32 = 600;
 // Get a null pointer (equivalent to an offset of 0)
 // ptr::null()
 
-unsafe fn setup_vao(vertices: &Vec<f32>, indices: &Vec<u32>) -> u32 {
-    let mut vao = 0;
-    let mut vbo = 0;
-    let mut ibo = 0;
-
-    // Generate VAO
-    gl::GenVertexArrays(1, &mut vao);
-    gl::BindVertexArray(vao);
-
-    //Generate vertex and index buffers
-    gl::GenBuffers(1, &mut vbo);
-    gl::GenBuffers(1, &mut ibo);
-
-    //Fill vertex buffer
-    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-    gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(vertices), util::pointer_to_array(vertices), gl::STATIC_DRAW);
-
-    // Fill index buffer
-    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
-    gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, util::byte_size_of_array(indices), util::pointer_to_array(indices), gl::STATIC_DRAW);
+// A vertex of the triangles, as it is stored in the vertex buffer
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct ColoredVertex {
+    position: glm::Vec3,
+    color: glm::Vec4,
+}
 
-    // Configure vertex attribute layout
-    gl::EnableVertexAttribArray(0);
-    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 7, ptr::null());
+unsafe impl Pod for ColoredVertex {}
 
-    gl::EnableVertexAttribArray(1);
-    gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 7, (3 * util::size_of::<f32>()) as *const gl::types::GLvoid);
-    gl::BindVertexArray(0);
-    return vao;
-}
+vertex_layout!(ColoredVertex {
+    position => "position" @ 0,
+    color => "color" @ 1,
+});
 
 fn main() {
     // Set up the necessary objects to deal with windows and event handling
@@ -110,16 +98,17 @@ fn main() {
             );
         }
 
-        let vertices: Vec<f32> = vec![
-            0.0, 0.5, -3.0, 0.0, 1.0, 0.0, 0.5,
-            -0.5, 0.0, -3.0, 0.0, 1.0, 0.0, 0.5,
-            0.5, 0.0, -3.0, 0.0, 1.0, 0.0, 0.5,
-            0.4, 0.5, -2.0, 0.0, 0.0, 1.0, 0.5,
-            -0.1, 0.0, -2.0, 0.0, 0.0, 1.0, 0.5,
-            0.9, 0.0, -2.0, 0.0, 0.0, 1.0, 0.5,
-            -0.4, 0.5, -1.0, 1.0, 0.0, 0.0, 0.5,
-            -0.9, 0.0, -1.0, 1.0, 0.0, 0.0, 0.5,
-            0.1, 0.0, -1.0, 1.0, 0.0, 0.0, 0.5,
+        let vertex = |x, y, z, r, g, b, a| ColoredVertex { position: glm::vec3(x, y, z), color: glm::vec4(r, g, b, a) };
+        let vertices = vec![
+            vertex(0.0, 0.5, -3.0, 0.0, 1.0, 0.0, 0.5),
+            vertex(-0.5, 0.0, -3.0, 0.0, 1.0, 0.0, 0.5),
+            vertex(0.5, 0.0, -3.0, 0.0, 1.0, 0.0, 0.5),
+            vertex(0.4, 0.5, -2.0, 0.0, 0.0, 1.0, 0.5),
+            vertex(-0.1, 0.0, -2.0, 0.0, 0.0, 1.0, 0.5),
+            vertex(0.9, 0.0, -2.0, 0.0, 0.0, 1.0, 0.5),
+            vertex(-0.4, 0.5, -1.0, 1.0, 0.0, 0.0, 0.5),
+            vertex(-0.9, 0.0, -1.0, 1.0, 0.0, 0.0, 0.5),
+            vertex(0.1, 0.0, -1.0, 1.0, 0.0, 0.0, 0.5),
         ];
         let indices: Vec<u32> = vec![
             0, 1, 2,
@@ -127,19 +116,28 @@ fn main() {
             6, 7, 8,
         ];
         let vao = unsafe {
-            setup_vao(&vertices, &indices)
+            VertexArray::from_vertices(&vertices, &indices)
         };
 
         // Basic usage of shader helper
-        // The code below returns a shader object, which contains the field .program_id
+        // The code below returns a shader object, which contains the method .program_id()
         // The snippet is not enough to do the assignment, and will need to be modified (outside of just using the correct path), but it only needs to be called once
-        // shader::ShaderBuilder::new().attach_file("./path/to/shader").link();
-        let shader = unsafe {
-            shader::ShaderBuilder::new().attach_file("./shaders/simple.frag").attach_file("./shaders/simple.vert").link()
+        // shader::ShaderBuilder::new().attach_file("./path/to/shader")?.link()?;
+        let mut shader = unsafe {
+            shader::ShaderBuilder::new()
+                .attach_file("./shaders/simple.frag")
+                .and_then(|builder| builder.attach_file("./shaders/simple.vert"))
+                .and_then(|builder| builder.link())
+                .unwrap_or_else(|e| panic!("{}", e))
         };
 
-        let particle_shader = unsafe {
-            shader::ShaderBuilder::new().attach_file("./shaders/simple.frag").attach_file("./shaders/particles.vert").link()
+        // Both particle systems draw with the same program
+        let particle_shader: ShaderHandle = unsafe {
+            Rc::new(RefCell::new(shader::ShaderBuilder::new()
+                .attach_file("./shaders/simple.frag")
+                .and_then(|builder| builder.attach_file("./shaders/particles.vert"))
+                .and_then(|builder| builder.link())
+                .unwrap_or_else(|e| panic!("{}", e))))
         };
 
         let projection = glm::perspective(800 as f32 / SCREEN_H as f32, (3.14 / 180.0) * 60.0, 1.0, 100.0);
@@ -163,7 +161,7 @@ fn main() {
                 } else {
                     current + (glm::vec4(1.0, 0.5, 0.0, 1.0) * timestep)
                 },
-            particle_shader);
+            Rc::clone(&particle_shader));
 
         let mut particle_system2 = ParticleSystem::new(
             1000,
@@ -183,7 +181,7 @@ fn main() {
                 } else {
                     current + (glm::vec4(0.1, 0.5, 0.0, 1.0) * timestep)
                 },
-            particle_shader);
+            Rc::clone(&particle_shader));
 
         // Used to demonstrate keyboard handling -- feel free to remove
         let mut _arbitrary_number = 0.0;
@@ -210,6 +208,12 @@ fn main() {
                 0.0, 0.0, 0.0, 1.0,
             );
 
+            // Rebuild the programs if particles.vert or the simple shaders were edited
+            unsafe {
+                shader.reload_if_changed();
+                particle_shader.borrow_mut().reload_if_changed();
+            }
+
             particle_system1.tick(delta_time);
             particle_system2.tick(delta_time);
 
@@ -272,7 +276,7 @@ fn main() {
                 particle_system2.render(&projection, &view);
 
 
-                gl::BindVertexArray(vao);
+                vao.bind();
                 shader.activate();
 
                 shader.set_uniform_mat4("projection", &projection);
@@ -1,9 +1,10 @@
 extern crate nalgebra_glm as glm;
 
 use std::time::{SystemTime, UNIX_EPOCH};
-use crate::shader::Shader;
-use crate::{util};
-use core::ptr;
+use gloom_engine::gl_object::{Buffer, Pod, VertexArray};
+use gloom_engine::material::ShaderHandle;
+use gloom_engine::vertex_layout::Vertex;
+use gloom_engine::util;
 
 #[derive(Copy, Clone)]
 struct Particle {
@@ -15,15 +16,41 @@ struct Particle {
     color_fun: fn(glm::Vec4, timestep: f32) -> glm::Vec4,
 }
 
+// A corner of the quad drawn for every particle
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct QuadVertex {
+    position: glm::Vec3,
+}
+
+unsafe impl Pod for QuadVertex {}
+
+vertex_layout!(QuadVertex { position => "position" @ 0 });
+
+// The per-instance data of a particle, as it is stored in the particle buffer
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct ParticleInstance {
+    position: glm::Vec3,
+    color: glm::Vec4,
+}
+
+unsafe impl Pod for ParticleInstance {}
+
+vertex_layout!(ParticleInstance {
+    position => "particle_position" @ 1,
+    color => "particle_color" @ 2,
+});
+
 pub struct ParticleSystem {
     particles: Vec<Particle>,
     position: glm::Vec3,
     radius: f32,
     life_min: f32,
     life_max: f32,
-    shader: Shader,
-    vao: u32,
-    vbos: [u32; 2],
+    shader: ShaderHandle,
+    vao: VertexArray,
+    particle_buffer: Buffer<ParticleInstance>,
 }
 
 impl Particle {
@@ -58,7 +85,7 @@ impl Particle {
 }
 
 impl ParticleSystem {
-    pub fn new(size: usize, position: glm::Vec3, radius: f32, life_min: f32, life_max: f32, velocity_fun: fn(glm::Vec3, f32) -> glm::Vec3, color_fun: fn(glm::Vec4, f32) -> glm::Vec4, shader: Shader) -> ParticleSystem {
+    pub fn new(size: usize, position: glm::Vec3, radius: f32, life_min: f32, life_max: f32, velocity_fun: fn(glm::Vec3, f32) -> glm::Vec3, color_fun: fn(glm::Vec4, f32) -> glm::Vec4, shader: ShaderHandle) -> ParticleSystem {
         let particles: Vec<Particle> = (0..size).into_iter().map(|_| Particle::new(
             position + glm::vec3(radius * util::generate_rng(-1.0, 1.0), radius * util::generate_rng(-1.0, 1.0), radius * util::generate_rng(-1.0, 1.0)),
             util::generate_rng(life_min, life_max),
@@ -66,43 +93,29 @@ impl ParticleSystem {
             color_fun,
         )).collect();
 
-        let mut vao = 0;
-        let mut vbos: [u32; 2] =  [0, 0];
-        let buffer: Vec<f32> = vec![
-            1.0, 1.0, 0.0,
-            -1.0, 1.0, 0.0,
-            1.0, -1.0, 0.0,
-            -1.0, -1.0, 0.0,
-
+        let quad = [
+            QuadVertex { position: glm::vec3(1.0, 1.0, 0.0) },
+            QuadVertex { position: glm::vec3(-1.0, 1.0, 0.0) },
+            QuadVertex { position: glm::vec3(1.0, -1.0, 0.0) },
+            QuadVertex { position: glm::vec3(-1.0, -1.0, 0.0) },
         ];
         unsafe {
             // Generate VAO
-            gl::GenVertexArrays(1, &mut vao);
-            gl::BindVertexArray(vao);
-
-            //Generate vertex and index buffersvbo
-            gl::GenBuffers(1,  &mut vbos[0]);
-            gl::GenBuffers(1,  &mut vbos[1]);
+            let mut vao = VertexArray::new();
+            vao.bind();
 
             //Fill vertex buffer
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbos[0]);
-            gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(&buffer), util::pointer_to_array(&buffer), gl::STATIC_DRAW);
+            let quad_buffer = Buffer::new(gl::ARRAY_BUFFER, &quad, gl::STATIC_DRAW);
+            QuadVertex::layout().enable(0);
 
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 3, ptr::null());
-
-            let particles_buffer = generate_buffer_data(&particles);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbos[1]);
-            gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(&particles_buffer), util::pointer_to_array(&particles_buffer), gl::STREAM_DRAW);
-
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 7, ptr::null());
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribPointer(2, 4, gl::FLOAT, gl::FALSE, util::size_of::<f32>() * 7, (3 * util::size_of::<f32>()) as *const gl::types::GLvoid);
+            // The quad is used repeatedly, while particle position and color moves down the buffer once per instance
+            let particle_buffer = Buffer::new(gl::ARRAY_BUFFER, &generate_buffer_data(&particles), gl::STREAM_DRAW);
+            ParticleInstance::layout().enable(1);
             gl::BindVertexArray(0);
-        }
+            vao.keep_alive(quad_buffer);
 
-        unsafe { ParticleSystem { particles, position: position.clone(), radius, life_min, life_max, shader, vao, vbos } }
+            ParticleSystem { particles, position: position.clone(), radius, life_min, life_max, shader, vao, particle_buffer }
+        }
     }
 
     pub fn tick(&mut self, timestep: f32) {
@@ -118,27 +131,20 @@ impl ParticleSystem {
     }
 
     unsafe fn updateBuffer(&mut self) {
-        let buffer_data = generate_buffer_data(&self.particles);
-        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbos[1]);
-        gl::BufferData(gl::ARRAY_BUFFER, util::byte_size_of_array(&buffer_data), ptr::null(), gl::STREAM_DRAW);
-        gl::BufferSubData(gl::ARRAY_BUFFER, 0, util::byte_size_of_array(&buffer_data), util::pointer_to_array(&buffer_data));
+        self.particle_buffer.stream(&generate_buffer_data(&self.particles));
     }
 
     pub unsafe fn render(&self, projection: &glm::Mat4, view: &glm::Mat4){
-        self.shader.activate();
-        self.shader.set_uniform_mat4("projection", projection);
-        self.shader.set_uniform_mat4("view", view);
-        gl::BindVertexArray(self.vao);
-
-        // Make sure the vertex position buffer is used repeatedly, while particle poistion and color moves down the buffer
-        gl::VertexAttribDivisor(0, 0);
-        gl::VertexAttribDivisor(1, 1);
-        gl::VertexAttribDivisor(2, 1);
+        let shader = self.shader.borrow();
+        shader.activate();
+        shader.set_uniform_mat4("projection", projection);
+        shader.set_uniform_mat4("view", view);
+        self.vao.bind();
 
         gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, self.particles.len() as i32);
     }
 }
 
-fn generate_buffer_data(particles: &Vec<Particle>) -> Vec<f32> {
-    particles.iter().flat_map(|p| vec!(p.position.x, p.position.y, p.position.z, p.color.x, p.color.y, p.color.z, p.color.w)).collect()
+fn generate_buffer_data(particles: &Vec<Particle>) -> Vec<ParticleInstance> {
+    particles.iter().map(|p| ParticleInstance { position: p.position, color: p.color }).collect()
 }
\ No newline at end of file
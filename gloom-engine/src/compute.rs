@@ -0,0 +1,114 @@
+use crate::shader::{Shader, ShaderBuilder, ShaderError};
+use crate::gl_object::{Buffer, Pod};
+
+// A program made from a single compute shader, together with the work group size declared by its local_size layout
+pub struct ComputeShader {
+    pub shader: Shader,
+    pub work_group_size: [u32; 3],
+}
+
+impl ComputeShader {
+    // Build a compute program from a .comp file
+    pub unsafe fn new(path: &str) -> Result<ComputeShader, ShaderError> {
+        ShaderBuilder::new()
+            .attach_file(path)
+            .and_then(|builder| builder.link())
+            .map(|shader| ComputeShader::from_shader(shader))
+    }
+
+    // Wrap a program built with ShaderBuilder, e.g. when it needs defines
+    pub unsafe fn from_shader(shader: Shader) -> ComputeShader {
+        let work_group_size = work_group_size(shader.program_id());
+        ComputeShader { shader, work_group_size }
+    }
+
+    // Run the given number of work groups. Results are only visible to later reads after the matching barrier.
+    pub unsafe fn dispatch(&self, groups_x: u32, groups_y: u32, groups_z: u32) {
+        self.shader.activate();
+        gl::DispatchCompute(groups_x, groups_y, groups_z);
+    }
+
+    // Run enough work groups to cover at least the given number of invocations in each dimension.
+    // The shader has to ignore the invocations past the end itself.
+    pub unsafe fn dispatch_invocations(&self, x: u32, y: u32, z: u32) {
+        let groups = |count: u32, size: u32| (count + size - 1) / size;
+        self.dispatch(
+            groups(x, self.work_group_size[0]),
+            groups(y, self.work_group_size[1]),
+            groups(z, self.work_group_size[2]),
+        );
+    }
+
+    pub unsafe fn reload_if_changed(&mut self) -> bool {
+        let reloaded = self.shader.reload_if_changed();
+        if reloaded {
+            self.work_group_size = work_group_size(self.shader.program_id());
+        }
+        reloaded
+    }
+}
+
+unsafe fn work_group_size(program_id: u32) -> [u32; 3] {
+    let mut size = [1i32; 3];
+    gl::GetProgramiv(program_id, gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
+    [size[0].max(1) as u32, size[1].max(1) as u32, size[2].max(1) as u32]
+}
+
+// Barriers to put between a dispatch and whatever reads what it wrote
+pub unsafe fn memory_barrier(barriers: gl::types::GLbitfield) {
+    gl::MemoryBarrier(barriers);
+}
+
+// Storage buffers written by one dispatch and read by the next
+pub unsafe fn storage_barrier() {
+    memory_barrier(gl::SHADER_STORAGE_BARRIER_BIT);
+}
+
+// Storage buffers written by a dispatch and then drawn as vertex or index data
+pub unsafe fn vertex_barrier() {
+    memory_barrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT | gl::ELEMENT_ARRAY_BARRIER_BIT);
+}
+
+// Images written with imageStore and then sampled or read back
+pub unsafe fn image_barrier() {
+    memory_barrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT | gl::TEXTURE_UPDATE_BARRIER_BIT);
+}
+
+// A shader storage buffer holding an array of T, bound to a fixed binding point.
+// T has to be #[repr(C)] and laid out like the std430 block it is read as.
+pub struct StorageBuffer<T: Pod> {
+    pub buffer: Buffer<T>,
+    pub binding: u32,
+}
+
+impl<T: Pod> StorageBuffer<T> {
+    pub unsafe fn new(binding: u32, data: &[T]) -> StorageBuffer<T> {
+        let buffer = StorageBuffer { buffer: Buffer::new(gl::SHADER_STORAGE_BUFFER, data, gl::DYNAMIC_COPY), binding };
+        gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        buffer.bind();
+        buffer
+    }
+
+    // Replace the content of the buffer, growing or shrinking it to fit
+    pub unsafe fn upload(&mut self, data: &[T]) {
+        self.buffer.set_data(data);
+        gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+    }
+
+    // Copy the content of the buffer back to the CPU. Call storage_barrier or memory_barrier(gl::BUFFER_UPDATE_BARRIER_BIT) first
+    // if a dispatch wrote to it.
+    pub unsafe fn read(&self) -> Vec<T> {
+        let data = self.buffer.read();
+        gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        data
+    }
+
+    // Bind the buffer to its binding point again, in case something else was bound there in the meantime
+    pub unsafe fn bind(&self) {
+        gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, self.binding, self.buffer.id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+}
@@ -0,0 +1,232 @@
+extern crate nalgebra_glm as glm;
+
+use std::any::Any;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use core::ptr;
+use crate::util;
+use crate::vertex_layout::Vertex;
+
+// Owned OpenGL objects. Each wrapper deletes its object when dropped, so sharing one between several owners
+// (e.g. a VAO used by many scene nodes) is done with an Rc handle rather than by copying the id.
+// They must be created and dropped on the thread that owns the OpenGL context.
+
+pub type Handle<T> = Rc<T>;
+
+pub struct VertexArray {
+    pub id: u32,
+    // Buffers the VAO reads from, kept alive for as long as the VAO is
+    buffers: Vec<Box<dyn Any>>,
+}
+
+impl VertexArray {
+    pub unsafe fn new() -> VertexArray {
+        let mut id = 0;
+        gl::GenVertexArrays(1, &mut id);
+        VertexArray { id, buffers: vec![] }
+    }
+
+    // Create a VAO drawing the given vertices and indices, with the attributes described by the layout of V.
+    // The vertex and index buffers are owned by the VAO.
+    pub unsafe fn from_vertices<V: Vertex>(vertices: &[V], indices: &[u32]) -> VertexArray {
+        let mut vao = VertexArray::new();
+        vao.bind();
+
+        let vbo = Buffer::new(gl::ARRAY_BUFFER, vertices, gl::STATIC_DRAW);
+        let ibo = Buffer::new(gl::ELEMENT_ARRAY_BUFFER, indices, gl::STATIC_DRAW);
+        V::layout().enable(0);
+        gl::BindVertexArray(0);
+
+        vao.keep_alive(vbo);
+        vao.keep_alive(ibo);
+        vao
+    }
+
+    pub unsafe fn bind(&self) {
+        gl::BindVertexArray(self.id);
+    }
+
+    // Hand ownership of a buffer to the VAO, for buffers that are only referenced through its attribute pointers
+    pub fn keep_alive<T: Pod>(&mut self, buffer: Buffer<T>) {
+        self.buffers.push(Box::new(buffer));
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteVertexArrays(1, &self.id) }
+    }
+}
+
+// Types that can be copied to and from GPU buffers as plain bytes: no pointers, references or Drop, and every bit pattern valid.
+// Structs implementing it must be #[repr(C)] and have no padding between or after their fields, which would be
+// uninitialized bytes. Vertex structs implement it themselves, next to their vertex_layout!.
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for f32 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u8 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+unsafe impl Pod for glm::Vec2 {}
+unsafe impl Pod for glm::Vec3 {}
+unsafe impl Pod for glm::Vec4 {}
+unsafe impl Pod for glm::Mat3 {}
+unsafe impl Pod for glm::Mat4 {}
+
+// A buffer object holding an array of T
+pub struct Buffer<T: Pod> {
+    pub id: u32,
+    pub target: gl::types::GLenum,
+    usage: gl::types::GLenum,
+    length: usize,
+    data: PhantomData<T>,
+}
+
+impl<T: Pod> Buffer<T> {
+    // Create a buffer and fill it with data, which may be empty. The buffer is left bound to target.
+    pub unsafe fn new(target: gl::types::GLenum, data: &[T], usage: gl::types::GLenum) -> Buffer<T> {
+        let mut id = 0;
+        gl::GenBuffers(1, &mut id);
+        let mut buffer = Buffer { id, target, usage, length: 0, data: PhantomData };
+        buffer.set_data(data);
+        buffer
+    }
+
+    // Create a buffer with room for length elements, without initializing them. The buffer is left bound to target.
+    pub unsafe fn with_length(target: gl::types::GLenum, length: usize, usage: gl::types::GLenum) -> Buffer<T> {
+        let mut id = 0;
+        gl::GenBuffers(1, &mut id);
+        gl::BindBuffer(target, id);
+        gl::BufferData(target, byte_size::<T>(length), ptr::null(), usage);
+        Buffer { id, target, usage, length, data: PhantomData }
+    }
+
+    pub unsafe fn bind(&self) {
+        gl::BindBuffer(self.target, self.id);
+    }
+
+    // Replace the content of the buffer, reallocating it to fit. The buffer is left bound to its target.
+    pub unsafe fn set_data(&mut self, data: &[T]) {
+        self.bind();
+        gl::BufferData(self.target, util::byte_size_of_array(data), util::pointer_to_array(data), self.usage);
+        self.length = data.len();
+    }
+
+    // Overwrite the elements starting at offset, without reallocating. Panics if data doesn't fit in the buffer.
+    pub unsafe fn update(&mut self, offset: usize, data: &[T]) {
+        assert!(offset + data.len() <= self.length,
+            "Buffer update of {} elements at {} is out of bounds for a buffer of {}", data.len(), offset, self.length);
+        if data.is_empty() {
+            return;
+        }
+        self.bind();
+        gl::BufferSubData(self.target, byte_size::<T>(offset), util::byte_size_of_array(data), util::pointer_to_array(data));
+    }
+
+    // Replace the content of a buffer that is rewritten every frame. The old storage is orphaned, so the driver can hand
+    // out fresh memory instead of waiting for draws that still read the previous content.
+    pub unsafe fn stream(&mut self, data: &[T]) {
+        if data.len() != self.length {
+            self.set_data(data);
+            return;
+        }
+        self.bind();
+        gl::BufferData(self.target, util::byte_size_of_array(data), ptr::null(), self.usage);
+        gl::BufferSubData(self.target, 0, util::byte_size_of_array(data), util::pointer_to_array(data));
+    }
+
+    // Copy the content of the buffer back to the CPU
+    pub unsafe fn read(&self) -> Vec<T> {
+        let mut data = Vec::with_capacity(self.length);
+        self.bind();
+        gl::GetBufferSubData(self.target, 0, byte_size::<T>(self.length), data.as_mut_ptr() as *mut gl::types::GLvoid);
+        data.set_len(self.length);
+        data
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
+
+fn byte_size<T>(length: usize) -> isize {
+    (length * std::mem::size_of::<T>()) as isize
+}
+
+impl<T: Pod> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.id) }
+    }
+}
+
+pub struct Texture {
+    pub id: u32,
+    pub target: gl::types::GLenum,
+}
+
+impl Texture {
+    // Create a texture and bind it to target
+    pub unsafe fn new(target: gl::types::GLenum) -> Texture {
+        let mut id = 0;
+        gl::GenTextures(1, &mut id);
+        gl::BindTexture(target, id);
+        Texture { id, target }
+    }
+
+    pub unsafe fn bind(&self, unit: u32) {
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(self.target, self.id);
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
+
+pub struct Framebuffer {
+    pub id: u32,
+}
+
+impl Framebuffer {
+    // Create a framebuffer and bind it to FRAMEBUFFER
+    pub unsafe fn new() -> Framebuffer {
+        let mut id = 0;
+        gl::GenFramebuffers(1, &mut id);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+        Framebuffer { id }
+    }
+
+    pub unsafe fn bind(&self) {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &self.id) }
+    }
+}
+
+pub struct Program {
+    pub id: u32,
+}
+
+impl Program {
+    pub unsafe fn new() -> Program {
+        Program { id: gl::CreateProgram() }
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteProgram(self.id) }
+    }
+}
@@ -0,0 +1,17 @@
+// The OpenGL helpers shared by the assignments: owned GL objects, vertex layouts, shaders, textures, materials and the
+// asset loader. Everything here has to be used on the thread that owns the OpenGL context, apart from the parsing
+// done by the loader workers.
+extern crate nalgebra_glm as glm;
+
+#[macro_use]
+pub mod vertex_layout;
+pub mod gl_object;
+pub mod util;
+pub mod watcher;
+pub mod loader;
+pub mod preprocessor;
+pub mod program_cache;
+pub mod shader;
+pub mod compute;
+pub mod texture;
+pub mod material;
@@ -0,0 +1,177 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::path::PathBuf;
+
+use crate::gl_object::{Handle, Texture};
+use crate::texture::{TextureCache, TextureData, TextureOptions};
+use crate::watcher::{self, FileWatcher};
+
+type Job = Box<dyn FnOnce() -> Box<dyn Any + Send> + Send>;
+type Upload = Box<dyn FnOnce(Box<dyn Any + Send>)>;
+type Reload = Box<dyn Fn() -> (Job, Upload)>;
+
+// Loads assets on a pool of worker threads. Parsing happens on the workers, while the part of loading that needs
+// the OpenGL context is queued and run on the render thread by process_uploads.
+// Assets loaded with load_watched are loaded again when one of their files changes, once watch has been called.
+// Only the newest load of an asset is uploaded, so a slow load can't replace the result of a later one.
+// Textures are cached, so everything using the same image shares one texture.
+pub struct AssetLoader {
+    jobs: Sender<(usize, Job)>,
+    results: Receiver<(usize, Option<Box<dyn Any + Send>>)>,
+    uploads: HashMap<usize, Upload>,
+    next_id: usize,
+    watcher: Option<FileWatcher>,
+    watched: Vec<(Vec<PathBuf>, Reload)>,
+    textures: TextureCache,
+}
+
+impl AssetLoader {
+    pub fn new(worker_count: usize) -> AssetLoader {
+        let (jobs, job_receiver) = mpsc::channel::<(usize, Job)>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        for _ in 0..worker_count {
+            let job_receiver = Arc::clone(&job_receiver);
+            let result_sender = result_sender.clone();
+            thread::spawn(move || loop {
+                let job = job_receiver.lock().unwrap().recv();
+                match job {
+                    Ok((id, job)) => {
+                        // Keep the worker alive if a loader panics on a broken file
+                        let result = panic::catch_unwind(AssertUnwindSafe(job)).ok();
+                        if result_sender.send((id, result)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            });
+        }
+
+        AssetLoader {
+            jobs,
+            results,
+            uploads: HashMap::new(),
+            next_id: 0,
+            watcher: None,
+            watched: vec![],
+            textures: TextureCache::new(),
+        }
+    }
+
+    // Run parse on a worker thread, and upload with its result on the render thread once it is done.
+    pub fn load<T, P, U>(&mut self, parse: P, upload: U)
+        where T: Send + 'static,
+              P: FnOnce() -> T + Send + 'static,
+              U: FnOnce(T) + 'static
+    {
+        self.queue(
+            Box::new(move || Box::new(parse()) as Box<dyn Any + Send>),
+            Box::new(move |data: Box<dyn Any + Send>| upload(*data.downcast::<T>().unwrap())),
+        );
+    }
+
+    // Same as load, but parse and upload are run again whenever one of the given files changes.
+    pub fn load_watched<T, P, U>(&mut self, paths: &[&str], parse: P, upload: U)
+        where T: Send + 'static,
+              P: Fn() -> T + Send + Sync + 'static,
+              U: Fn(T) + 'static
+    {
+        let parse = Arc::new(parse);
+        let upload = Rc::new(upload);
+        let latest = Rc::new(Cell::new(0u64));
+        let reload: Reload = Box::new(move || {
+            let parse = Arc::clone(&parse);
+            let upload = Rc::clone(&upload);
+            let latest = Rc::clone(&latest);
+            let generation = latest.get() + 1;
+            latest.set(generation);
+            (
+                Box::new(move || Box::new(parse()) as Box<dyn Any + Send>),
+                Box::new(move |data: Box<dyn Any + Send>| {
+                    if latest.get() == generation {
+                        upload(*data.downcast::<T>().unwrap());
+                    }
+                }),
+            )
+        });
+
+        let (job, upload) = reload();
+        self.queue(job, upload);
+        // Kept as given, since a file that doesn't exist yet can't be made absolute until it is created
+        self.watched.push((paths.iter().map(PathBuf::from).collect(), reload));
+    }
+
+    // Start watching a directory for changes to files with the given extensions.
+    pub fn watch(&mut self, directory: &str, extensions: &[&str]) {
+        self.watcher = Some(FileWatcher::new(directory, extensions));
+    }
+
+    // Load a texture, or get the cached one if the image was already loaded with the same options.
+    // The texture is created right away and has no image until the upload is done. When the image changes it is
+    // uploaded again into the same texture, so everything using it is updated.
+    pub unsafe fn load_texture(&mut self, path: &str, options: TextureOptions) -> Handle<Texture> {
+        if let Some(texture) = self.textures.get(path, &options) {
+            return texture;
+        }
+
+        let texture = Handle::new(Texture::new(gl::TEXTURE_2D));
+        self.textures.insert(path, &options, Handle::clone(&texture));
+        let target = Rc::downgrade(&texture);
+        let image_path = path.to_string();
+
+        self.load_watched(&[path], move || {
+            TextureData::load(&image_path, options.format).unwrap_or_else(|e| panic!("{}", e))
+        }, move |data: TextureData| {
+            if let Some(texture) = target.upgrade() {
+                unsafe { data.upload(&texture, &options) };
+            }
+        });
+
+        texture
+    }
+
+    // Run the uploads of everything that finished loading since the last call. Must be called on the render thread.
+    pub fn process_uploads(&mut self) {
+        if let Some(watcher) = &mut self.watcher {
+            let changed = watcher.changed();
+            let reloads = self.watched.iter()
+                .filter(|(paths, _)| {
+                    !changed.is_empty() && paths.iter().any(|p| changed.contains(&watcher::canonical(p)))
+                })
+                .map(|(_, reload)| reload())
+                .collect::<Vec<(Job, Upload)>>();
+            for (job, upload) in reloads {
+                self.queue(job, upload);
+            }
+        }
+
+        while let Ok((id, result)) = self.results.try_recv() {
+            let upload = self.uploads.remove(&id).unwrap();
+            match result {
+                Some(data) => upload(data),
+                None => println!("Failed to load asset, keeping the current version."),
+            }
+        }
+    }
+
+    fn queue(&mut self, job: Job, upload: Upload) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.uploads.insert(id, upload);
+        self.jobs.send((id, job)).expect("Asset loader workers have stopped");
+    }
+
+    // Number of assets that are still being loaded.
+    pub fn pending(&self) -> usize {
+        self.uploads.len()
+    }
+}
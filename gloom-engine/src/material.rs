@@ -0,0 +1,163 @@
+extern crate nalgebra_glm as glm;
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use crate::shader::{Shader, Uniform};
+use crate::texture::TextureSet;
+
+// Shaders are shared between materials, and borrowed mutably to reload them
+pub type ShaderHandle = Rc<RefCell<Shader>>;
+
+// A value of a material parameter, set as a uniform of the material's shader
+#[derive(Debug, Copy, Clone)]
+pub enum UniformValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    Vec2(glm::Vec2),
+    Vec3(glm::Vec3),
+    Vec4(glm::Vec4),
+    Mat3(glm::Mat3),
+    Mat4(glm::Mat4),
+}
+
+impl Uniform for UniformValue {
+    unsafe fn set(&self, location: i32) {
+        match self {
+            UniformValue::Bool(value) => value.set(location),
+            UniformValue::Int(value) => value.set(location),
+            UniformValue::Float(value) => value.set(location),
+            UniformValue::Vec2(value) => value.set(location),
+            UniformValue::Vec3(value) => value.set(location),
+            UniformValue::Vec4(value) => value.set(location),
+            UniformValue::Mat3(value) => value.set(location),
+            UniformValue::Mat4(value) => value.set(location),
+        }
+    }
+}
+
+macro_rules! impl_from_value {
+    ($type:ty, $variant:ident) => {
+        impl From<$type> for UniformValue {
+            fn from(value: $type) -> UniformValue {
+                UniformValue::$variant(value)
+            }
+        }
+    };
+}
+
+impl_from_value!(bool, Bool);
+impl_from_value!(i32, Int);
+impl_from_value!(f32, Float);
+impl_from_value!(glm::Vec2, Vec2);
+impl_from_value!(glm::Vec3, Vec3);
+impl_from_value!(glm::Vec4, Vec4);
+impl_from_value!(glm::Mat3, Mat3);
+impl_from_value!(glm::Mat4, Mat4);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    Additive,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+// Fixed function state set before drawing with a material. The default is what the scene used before materials:
+// alpha blending, back face culling and depth testing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderState {
+    pub blend: BlendMode,
+    pub cull: CullMode,
+    pub depth_test: bool,
+    pub depth_write: bool,
+}
+
+impl Default for RenderState {
+    fn default() -> RenderState {
+        RenderState { blend: BlendMode::Alpha, cull: CullMode::Back, depth_test: true, depth_write: true }
+    }
+}
+
+impl RenderState {
+    pub unsafe fn apply(&self) {
+        match self.blend {
+            BlendMode::Opaque => gl::Disable(gl::BLEND),
+            BlendMode::Alpha => {
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            }
+            BlendMode::Additive => {
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE);
+            }
+        }
+        match self.cull {
+            CullMode::None => gl::Disable(gl::CULL_FACE),
+            CullMode::Back => {
+                gl::Enable(gl::CULL_FACE);
+                gl::CullFace(gl::BACK);
+            }
+            CullMode::Front => {
+                gl::Enable(gl::CULL_FACE);
+                gl::CullFace(gl::FRONT);
+            }
+        }
+        if self.depth_test {
+            gl::Enable(gl::DEPTH_TEST);
+        } else {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        gl::DepthMask(if self.depth_write { gl::TRUE } else { gl::FALSE });
+    }
+}
+
+// Everything needed to draw a mesh apart from the mesh itself: the shader, the values of its uniforms, its textures
+// and the render state. Materials are shared between nodes through Handle<Material>.
+// Parameters are set every time the material is applied, but uniforms a material doesn't set keep the value
+// the last material using the same shader gave them.
+#[derive(Clone)]
+pub struct Material {
+    pub shader: ShaderHandle,
+    pub parameters: Vec<(String, UniformValue)>,
+    pub textures: TextureSet,
+    pub state: RenderState,
+}
+
+impl Material {
+    pub fn new(shader: ShaderHandle) -> Material {
+        Material { shader, parameters: vec![], textures: TextureSet::new(), state: RenderState::default() }
+    }
+
+    // Set a parameter, replacing its current value
+    pub fn set<T: Into<UniformValue>>(&mut self, name: &str, value: T) {
+        let value = value.into();
+        match self.parameters.iter_mut().find(|(parameter, _)| parameter == name) {
+            Some(entry) => entry.1 = value,
+            None => self.parameters.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<UniformValue> {
+        self.parameters.iter().find(|(parameter, _)| parameter == name).map(|&(_, value)| value)
+    }
+
+    // Activate the shader and set the parameters, textures and render state. Returns the shader so per draw uniforms
+    // can be set, and the number of texture units used by the material.
+    pub unsafe fn apply(&self) -> (Ref<'_, Shader>, u32) {
+        let shader = self.shader.borrow();
+        shader.activate();
+        for (name, value) in &self.parameters {
+            shader.set_uniform(name, value);
+        }
+        let texture_units = self.textures.bind(&shader, 0);
+        self.state.apply();
+        (shader, texture_units)
+    }
+}
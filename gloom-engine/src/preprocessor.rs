@@ -0,0 +1,91 @@
+use std::path::Path;
+use crate::shader::ShaderError;
+
+// GLSL source with #include directives resolved and #defines injected.
+// Every line of the source remembers the file and line it came from, so compiler errors can be mapped back.
+pub struct Preprocessed {
+    pub source: String,
+    pub origins: Vec<(String, usize)>,
+    pub files: Vec<String>,
+}
+
+impl Preprocessed {
+    // The file and line that line number `line` of the expanded source came from
+    pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+        self.origins.get(line.wrapping_sub(1)).map(|(file, line)| (file.as_str(), *line))
+    }
+}
+
+// Preprocess a shader file. Includes are resolved relative to the file containing them.
+pub fn preprocess_file(path: &str, defines: &[(String, String)]) -> Result<Preprocessed, ShaderError> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|error| ShaderError::Io { path: path.to_string(), error })?;
+    preprocess(path, &source, defines)
+}
+
+// Preprocess shader source that isn't read from a file. Includes are resolved relative to the working directory.
+pub fn preprocess_source(source: &str, defines: &[(String, String)]) -> Result<Preprocessed, ShaderError> {
+    preprocess("<source>", source, defines)
+}
+
+fn preprocess(name: &str, source: &str, defines: &[(String, String)]) -> Result<Preprocessed, ShaderError> {
+    let mut lines = vec![];
+    let mut origins = vec![];
+    let mut files = vec![];
+    expand(name, source, &mut vec![], &mut lines, &mut origins, &mut files)?;
+
+    // #version has to come first, so the defines go right after it
+    let position = lines.iter().position(|l| l.trim_start().starts_with("#version")).map_or(0, |i| i + 1);
+    for (i, (define, value)) in defines.iter().enumerate() {
+        lines.insert(position + i, format!("#define {} {}", define, value));
+        origins.insert(position + i, ("<define>".to_string(), i + 1));
+    }
+
+    Ok(Preprocessed {
+        source: lines.join("\n"),
+        origins,
+        files,
+    })
+}
+
+fn expand(
+    name: &str,
+    source: &str,
+    stack: &mut Vec<String>,
+    lines: &mut Vec<String>,
+    origins: &mut Vec<(String, usize)>,
+    files: &mut Vec<String>,
+) -> Result<(), ShaderError> {
+    stack.push(name.to_string());
+    if name != "<source>" {
+        files.push(name.to_string());
+    }
+
+    for (i, line) in source.lines().enumerate() {
+        let directive = line.trim();
+        if !directive.starts_with("#include") {
+            lines.push(line.to_string());
+            origins.push((name.to_string(), i + 1));
+            continue;
+        }
+
+        let include_error = |message: &str| ShaderError::Include { path: name.to_string(), line: i + 1, message: message.to_string() };
+        let file = directive["#include".len()..].trim();
+        if file.len() < 2 || !file.starts_with('"') || !file.ends_with('"') {
+            return Err(include_error("Expected #include \"file\""));
+        }
+
+        let directory = if name == "<source>" { Path::new("") } else { Path::new(name).parent().unwrap_or(Path::new("")) };
+        let included = directory.join(&file[1..file.len() - 1]).to_string_lossy().to_string();
+        if stack.contains(&included) {
+            return Err(include_error(&format!("{} includes itself", included)));
+        }
+
+        let included_source = std::fs::read_to_string(&included)
+            .map_err(|e| include_error(&format!("Failed to read {}: {}", included, e)))?;
+        expand(&included, &included_source, stack, lines, origins, files)?;
+    }
+
+    stack.pop();
+    Ok(())
+}
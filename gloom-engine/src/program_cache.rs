@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::util::{self, fnv1a, FNV_OFFSET};
+
+// Linked program binaries are stored in cache/programs/<hash>.bin, where the hash covers the preprocessed sources of the
+// program and the vendor, renderer and version strings of the driver. A binary is only valid for the driver that produced
+// it, so updating the driver or switching GPU results in a new entry. The file holds the binary format as a little endian
+// u32 followed by the binary itself.
+const CACHE_DIR: &str = "cache/programs";
+
+// The cache key of a program built from the given stages and preprocessed sources.
+// Needs a current OpenGL context, since the driver strings are part of the key.
+pub unsafe fn key(sources: &[(gl::types::GLenum, &str)]) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for &name in &[gl::VENDOR, gl::RENDERER, gl::VERSION] {
+        hash = fnv1a(hash, util::get_gl_string(name).as_bytes());
+    }
+    for (stage, source) in sources {
+        hash = fnv1a(hash, &stage.to_le_bytes());
+        hash = fnv1a(hash, source.as_bytes());
+    }
+    hash
+}
+
+// Whether the driver supports program binaries at all
+pub unsafe fn supported() -> bool {
+    let mut formats = 0;
+    gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+    formats > 0
+}
+
+// The binary format and binary stored for key, if any
+pub fn load(key: u64) -> Option<(u32, Vec<u8>)> {
+    let data = fs::read(path(key)).ok()?;
+    if data.len() < 4 {
+        return None;
+    }
+    let mut format = [0; 4];
+    format.copy_from_slice(&data[..4]);
+    Some((u32::from_le_bytes(format), data[4..].to_vec()))
+}
+
+pub fn store(key: u64, format: u32, binary: &[u8]) {
+    let mut data = format.to_le_bytes().to_vec();
+    data.extend_from_slice(binary);
+    if let Err(e) = fs::create_dir_all(CACHE_DIR).and_then(|_| fs::write(path(key), &data)) {
+        println!("Failed to write program cache {}: {}", path(key).display(), e);
+    }
+}
+
+// Remove an entry the driver rejected, so it is replaced by the next successful link
+pub fn remove(key: u64) {
+    let _ = fs::remove_file(path(key));
+}
+
+fn path(key: u64) -> PathBuf {
+    PathBuf::from(CACHE_DIR).join(format!("{:016x}.bin", key))
+}
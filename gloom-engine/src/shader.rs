@@ -0,0 +1,637 @@
+use gl;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    ptr,
+    str,
+    ffi::CString,
+    path::Path,
+    time::SystemTime,
+};
+use crate::preprocessor::{self, Preprocessed};
+use crate::program_cache;
+use crate::gl_object::Program;
+use crate::vertex_layout::VertexLayout;
+use std::rc::Rc;
+
+// Programs built from files remember their defines and the paths and modification times of their sources and includes,
+// so they can be rebuilt when one of them changes. Programs only built with compile_shader are never reloaded.
+#[derive(Clone)]
+pub struct Shader {
+    // Clones share the program, which is deleted when the last clone is dropped
+    program: Rc<Program>,
+    pub attributes: Vec<ActiveVariable>,
+    pub uniforms: Vec<ActiveVariable>,
+    locations: RefCell<HashMap<String, i32>>,
+    paths: Vec<String>,
+    defines: Vec<(String, String)>,
+    dependencies: Vec<(String, Option<SystemTime>)>,
+    cache_binary: bool,
+}
+
+pub struct ShaderBuilder {
+    program: Program,
+    shaders: Vec::<u32>,
+    paths: Vec<String>,
+    defines: Vec<(String, String)>,
+    dependencies: Vec<String>,
+    cache_binary: bool,
+    pending: Vec<(Preprocessed, ShaderType, Option<String>)>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ShaderType {
+    Vertex,
+    Fragment,
+    TessellationControl,
+    TessellationEvaluation,
+    Geometry,
+    Compute,
+}
+
+// An active attribute or uniform of a linked program, as reported by the driver
+#[derive(Debug, Clone)]
+pub struct ActiveVariable {
+    pub name: String,
+    pub gl_type: gl::types::GLenum,
+    pub size: i32,
+    pub location: i32,
+}
+
+// A line of a compiler log, mapped back to the file and line it refers to
+#[derive(Debug, Clone)]
+pub struct ErrorLine {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+    pub source: String,
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io { path: String, error: std::io::Error },
+    UnknownExtension { path: String },
+    Include { path: String, line: usize, message: String },
+    Compile { stage: ShaderType, path: Option<String>, log: String, lines: Vec<ErrorLine> },
+    Link { paths: Vec<String>, log: String },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, error } => write!(f, "Failed to read shader source {}: {}", path, error),
+            ShaderError::UnknownExtension { path } => write!(f, "Failed to parse file extension of shader {}", path),
+            ShaderError::Include { path, line, message } => write!(f, "{}:{}: {}", path, line, message),
+            ShaderError::Compile { stage, path, log, lines } => {
+                writeln!(f, "{:?} shader {} failed to compile:", stage, path.as_deref().unwrap_or("<source>"))?;
+                if lines.is_empty() {
+                    return write!(f, "{}", log);
+                }
+                for line in lines {
+                    writeln!(f, "{}:{}", line.file, line.line)?;
+                    writeln!(f, "    | {}", line.source)?;
+                    writeln!(f, "    = {}", line.message)?;
+                }
+                Ok(())
+            }
+            ShaderError::Link { paths, log } => write!(f, "Shader program [{}] failed to link:\n{}", paths.join(", "), log),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// A value that can be uploaded to a uniform of the matching GLSL type.
+// Slices upload to uniform arrays, e.g. &lights[..] to "uniform vec3 lights[4]".
+pub trait Uniform {
+    unsafe fn set(&self, location: i32);
+}
+
+// The texture unit a sampler uniform reads from
+#[derive(Debug, Copy, Clone)]
+pub struct Sampler(pub i32);
+
+macro_rules! impl_uniform {
+    ($type:ty, $component:ty, $function:ident) => {
+        impl Uniform for $type {
+            unsafe fn set(&self, location: i32) {
+                gl::$function(location, 1, self as *const $type as *const $component);
+            }
+        }
+
+        impl Uniform for [$type] {
+            unsafe fn set(&self, location: i32) {
+                gl::$function(location, self.len() as i32, self.as_ptr() as *const $component);
+            }
+        }
+    };
+}
+
+macro_rules! impl_uniform_matrix {
+    ($type:ty, $function:ident) => {
+        impl Uniform for $type {
+            unsafe fn set(&self, location: i32) {
+                gl::$function(location, 1, gl::FALSE, self as *const $type as *const f32);
+            }
+        }
+
+        impl Uniform for [$type] {
+            unsafe fn set(&self, location: i32) {
+                gl::$function(location, self.len() as i32, gl::FALSE, self.as_ptr() as *const f32);
+            }
+        }
+    };
+}
+
+impl_uniform!(f32, f32, Uniform1fv);
+impl_uniform!(i32, i32, Uniform1iv);
+impl_uniform!(u32, u32, Uniform1uiv);
+impl_uniform!(glm::Vec2, f32, Uniform2fv);
+impl_uniform!(glm::Vec3, f32, Uniform3fv);
+impl_uniform!(glm::Vec4, f32, Uniform4fv);
+impl_uniform!(glm::IVec2, i32, Uniform2iv);
+impl_uniform!(glm::IVec3, i32, Uniform3iv);
+impl_uniform!(glm::IVec4, i32, Uniform4iv);
+impl_uniform_matrix!(glm::Mat3, UniformMatrix3fv);
+impl_uniform_matrix!(glm::Mat4, UniformMatrix4fv);
+
+impl Uniform for bool {
+    unsafe fn set(&self, location: i32) {
+        gl::Uniform1i(location, *self as i32);
+    }
+}
+
+impl Uniform for [bool] {
+    unsafe fn set(&self, location: i32) {
+        let values: Vec<i32> = self.iter().map(|&b| b as i32).collect();
+        values[..].set(location);
+    }
+}
+
+impl Uniform for Sampler {
+    unsafe fn set(&self, location: i32) {
+        gl::Uniform1i(location, self.0);
+    }
+}
+
+impl Shader {
+    // Locations are cached, so only the first lookup of each name asks the driver
+    pub unsafe fn get_uniform_location(&self, name: &str) -> i32 {
+        if let Some(&location) = self.locations.borrow().get(name) {
+            return location;
+        }
+        let location = gl::GetUniformLocation(self.program.id, CString::new(name).expect("CString::new failed").as_ptr());
+        if location == -1 {
+            self.warn_unknown_uniform(name);
+        }
+        self.locations.borrow_mut().insert(name.to_string(), location);
+        location
+    }
+
+    fn warn_unknown_uniform(&self, name: &str) {
+        let suggestion = self.uniforms.iter()
+            .map(|u| u.name.trim_end_matches("[0]"))
+            .min_by_key(|candidate| edit_distance(name, candidate))
+            .filter(|candidate| edit_distance(name, candidate) <= 3)
+            .map(|candidate| format!(", did you mean {}?", candidate))
+            .unwrap_or_default();
+        println!("Warning: uniform {} is not an active uniform of shader [{}]{}", name, self.paths.join(", "), suggestion);
+    }
+
+    // Check that the attributes the vertex shader reads match the ones a mesh provides, printing a warning for every mismatch.
+    // Returns whether everything matched.
+    pub fn validate_layout(&self, layout: &VertexLayout) -> bool {
+        let mut valid = true;
+        let shader = self.paths.join(", ");
+        for attribute in &self.attributes {
+            match layout.attributes.iter().find(|a| a.location as i32 == attribute.location) {
+                None => {
+                    println!("Warning: attribute {} at location {} of shader [{}] is not provided by the mesh", attribute.name, attribute.location, shader);
+                    valid = false;
+                }
+                Some(provided) => {
+                    if provided.name != attribute.name {
+                        println!("Warning: location {} is {} in shader [{}], but {} in the mesh", attribute.location, attribute.name, shader, provided.name);
+                        valid = false;
+                    }
+                    if let Some(components) = component_count(attribute.gl_type) {
+                        if components != provided.components {
+                            println!("Warning: attribute {} of shader [{}] has {} components, but the mesh provides {}", attribute.name, shader, components, provided.components);
+                            valid = false;
+                        }
+                    }
+                }
+            }
+        }
+        valid
+    }
+
+    // Set a uniform of this program. Make sure the shader is active before calling this.
+    // Uniforms that don't exist or were optimized away are ignored by OpenGL.
+    pub unsafe fn set_uniform<T: Uniform + ?Sized>(&self, name: &str, value: &T) {
+        value.set(self.get_uniform_location(name));
+    }
+
+    // Get the uniform location and set it with the given matrix.
+    pub unsafe fn set_uniform_mat4(&self, name: &str, mat: &glm::Mat4){
+        self.set_uniform(name, mat);
+    }
+
+    // Bind the named shader storage block to a binding point, for blocks declared without layout(binding = ...).
+    // Returns false if the program has no such block.
+    pub unsafe fn storage_block_binding(&self, name: &str, binding: u32) -> bool {
+        let c_name = CString::new(name).expect("CString::new failed");
+        let index = gl::GetProgramResourceIndex(self.program.id, gl::SHADER_STORAGE_BLOCK, c_name.as_ptr());
+        if index == gl::INVALID_INDEX {
+            println!("Warning: storage block {} is not active in shader [{}]", name, self.paths.join(", "));
+            return false;
+        }
+        gl::ShaderStorageBlockBinding(self.program.id, index, binding);
+        true
+    }
+
+    // The id of the program, which changes when the shader is reloaded
+    pub fn program_id(&self) -> u32 {
+        self.program.id
+    }
+
+    pub unsafe fn activate(&self) {
+        gl::UseProgram(self.program.id);
+    }
+
+    // Recompile the program if one of its source files has changed since it was built.
+    // If the new version fails to build, the error is printed and the old program is kept.
+    // Uniforms have to be set again after a successful reload.
+    pub unsafe fn reload_if_changed(&mut self) -> bool {
+        let changed = self.dependencies.iter().any(|(path, modified)| &modification_time(path) != modified);
+        if !changed {
+            return false;
+        }
+
+        // Remember the new times even if the build fails, so a broken file isn't recompiled every frame
+        for (path, modified) in &mut self.dependencies {
+            *modified = modification_time(path);
+        }
+
+        let builder = if self.cache_binary { ShaderBuilder::new().cache_binary() } else { ShaderBuilder::new() };
+        let builder = self.defines.iter()
+            .fold(builder, |builder, (name, value)| builder.define(name, value));
+        let rebuilt = self.paths.iter()
+            .try_fold(builder, |builder, path| builder.attach_file(path))
+            .and_then(|builder| builder.link());
+
+        match rebuilt {
+            Ok(shader) => {
+                println!("Reloaded shader [{}]", self.paths.join(", "));
+                self.program = shader.program;
+                self.attributes = shader.attributes;
+                self.uniforms = shader.uniforms;
+                self.locations.borrow_mut().clear();
+                // Includes may have been added or removed
+                self.dependencies = shader.dependencies;
+                true
+            }
+            Err(e) => {
+                println!("{}", e);
+                false
+            }
+        }
+    }
+}
+
+fn modification_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Into<gl::types::GLenum> for ShaderType {
+    fn into(self) -> gl::types::GLenum {
+        match self {
+            ShaderType::Vertex                  => { gl::VERTEX_SHADER          },
+            ShaderType::Fragment                => { gl::FRAGMENT_SHADER        },
+            ShaderType::TessellationControl     => { gl::TESS_CONTROL_SHADER    },
+            ShaderType::TessellationEvaluation  => { gl::TESS_EVALUATION_SHADER } ,
+            ShaderType::Geometry                => { gl::GEOMETRY_SHADER        },
+            ShaderType::Compute                 => { gl::COMPUTE_SHADER         },
+        }
+    }
+}
+
+impl ShaderType {
+    pub fn from_ext(ext: &std::ffi::OsStr) -> Result<ShaderType, String> {
+        match ext.to_str().expect("Failed to read extension") {
+            "vert" => { Ok(ShaderType::Vertex) },
+            "frag" => { Ok(ShaderType::Fragment) },
+            "tcs"  => { Ok(ShaderType::TessellationControl) },
+            "tes"  => { Ok(ShaderType::TessellationEvaluation) },
+            "geom" => { Ok(ShaderType::Geometry) },
+            "comp" => { Ok(ShaderType::Compute) },
+            e => { Err(e.to_string()) },
+        }
+    }
+}
+
+impl ShaderBuilder {
+    pub unsafe fn new() -> ShaderBuilder {
+        ShaderBuilder {
+            program: Program::new(),
+            shaders: vec![],
+            paths: vec![],
+            defines: vec![],
+            dependencies: vec![],
+            cache_binary: false,
+            pending: vec![],
+        }
+    }
+
+    // Store the linked program binary in cache/programs and load it instead of compiling on the next run.
+    // Shaders attached after this are only compiled in link, and only if there is no cached binary the driver accepts.
+    pub fn cache_binary(mut self) -> ShaderBuilder {
+        self.cache_binary = true;
+        self
+    }
+
+    // Add "#define name value" after the #version line of every shader attached after this
+    pub fn define(mut self, name: &str, value: &str) -> ShaderBuilder {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub unsafe fn attach_file(self, shader_path: &str) -> Result<ShaderBuilder, ShaderError> {
+        let path = Path::new(shader_path);
+        let shader_type = match path.extension().map(ShaderType::from_ext) {
+            Some(Ok(shader_type)) => shader_type,
+            _ => {
+                self.delete();
+                return Err(ShaderError::UnknownExtension { path: shader_path.to_string() });
+            }
+        };
+        match preprocessor::preprocess_file(shader_path, &self.defines) {
+            Ok(preprocessed) => self.compile(preprocessed, shader_type, Some(shader_path)),
+            Err(e) => {
+                self.delete();
+                Err(e)
+            }
+        }
+    }
+
+    pub unsafe fn compile_shader(self, shader_src: &str, shader_type: ShaderType) -> Result<ShaderBuilder, ShaderError> {
+        match preprocessor::preprocess_source(shader_src, &self.defines) {
+            Ok(preprocessed) => self.compile(preprocessed, shader_type, None),
+            Err(e) => {
+                self.delete();
+                Err(e)
+            }
+        }
+    }
+
+    unsafe fn compile(mut self, preprocessed: Preprocessed, shader_type: ShaderType, path: Option<&str>) -> Result<ShaderBuilder, ShaderError> {
+        if let Some(path) = path {
+            self.paths.push(path.to_string());
+        }
+        self.dependencies.extend(preprocessed.files.iter().cloned());
+
+        if self.cache_binary {
+            self.pending.push((preprocessed, shader_type, path.map(|p| p.to_string())));
+            return Ok(self);
+        }
+        match compile_stage(&preprocessed, shader_type, path) {
+            Ok(shader) => {
+                self.shaders.push(shader);
+                Ok(self)
+            }
+            Err(e) => {
+                self.delete();
+                Err(e)
+            }
+        }
+    }
+
+    #[must_use = "The shader program is useless if not stored in a variable."]
+    pub unsafe fn link(mut self) -> Result<Shader, ShaderError> {
+        let cache_key = if self.cache_binary && program_cache::supported() {
+            let sources = self.pending.iter()
+                .map(|(preprocessed, shader_type, _)| ((*shader_type).into(), preprocessed.source.as_str()))
+                .collect::<Vec<(gl::types::GLenum, &str)>>();
+            Some(program_cache::key(&sources))
+        } else {
+            None
+        };
+
+        if !cache_key.map_or(false, |key| self.load_binary(key)) {
+            for (preprocessed, shader_type, path) in std::mem::take(&mut self.pending) {
+                match compile_stage(&preprocessed, shader_type, path.as_deref()) {
+                    Ok(shader) => self.shaders.push(shader),
+                    Err(e) => {
+                        self.delete();
+                        return Err(e);
+                    }
+                }
+            }
+
+            for &shader in &self.shaders {
+                gl::AttachShader(self.program.id, shader);
+            }
+            if cache_key.is_some() {
+                gl::ProgramParameteri(self.program.id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+            }
+            gl::LinkProgram(self.program.id);
+
+            let mut success = i32::from(gl::FALSE);
+            gl::GetProgramiv(self.program.id, gl::LINK_STATUS, &mut success);
+            if success != i32::from(gl::TRUE) {
+                let log = program_info_log(self.program.id);
+                let paths = self.paths.clone();
+                self.delete();
+                return Err(ShaderError::Link { paths, log });
+            }
+
+            if let Some(key) = cache_key {
+                self.store_binary(key);
+            }
+        }
+
+        for &shader in &self.shaders {
+            gl::DeleteShader(shader);
+        }
+
+        Ok(Shader {
+            attributes: active_variables(self.program.id, gl::ACTIVE_ATTRIBUTES),
+            uniforms: active_variables(self.program.id, gl::ACTIVE_UNIFORMS),
+            locations: RefCell::new(HashMap::new()),
+            dependencies: self.dependencies.iter().map(|p| (p.clone(), modification_time(p))).collect(),
+            program: Rc::new(self.program),
+            paths: self.paths,
+            defines: self.defines,
+            cache_binary: self.cache_binary,
+        })
+    }
+
+    // Load a cached binary into the program. Returns false if there is none, or if the driver rejects it.
+    unsafe fn load_binary(&self, key: u64) -> bool {
+        let (format, binary) = match program_cache::load(key) {
+            Some(cached) => cached,
+            None => return false,
+        };
+        gl::ProgramBinary(self.program.id, format, binary.as_ptr() as *const gl::types::GLvoid, binary.len() as i32);
+
+        let mut success = i32::from(gl::FALSE);
+        gl::GetProgramiv(self.program.id, gl::LINK_STATUS, &mut success);
+        if success != i32::from(gl::TRUE) {
+            program_cache::remove(key);
+            return false;
+        }
+        true
+    }
+
+    unsafe fn store_binary(&self, key: u64) {
+        let mut length = 0;
+        gl::GetProgramiv(self.program.id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        if length <= 0 {
+            return;
+        }
+
+        let mut binary = vec![0u8; length as usize];
+        let mut written = 0;
+        let mut format = 0;
+        gl::GetProgramBinary(self.program.id, length, &mut written, &mut format, binary.as_mut_ptr() as *mut gl::types::GLvoid);
+        binary.truncate(written as usize);
+        program_cache::store(key, format, &binary);
+    }
+
+    // Free the shaders of a builder that failed. The program is freed when the builder is dropped.
+    unsafe fn delete(&self) {
+        for &shader in &self.shaders {
+            gl::DeleteShader(shader);
+        }
+    }
+}
+
+unsafe fn compile_stage(preprocessed: &Preprocessed, shader_type: ShaderType, path: Option<&str>) -> Result<u32, ShaderError> {
+    let shader = gl::CreateShader(shader_type.into());
+    let c_str_shader = CString::new(preprocessed.source.as_bytes()).unwrap();
+    gl::ShaderSource(shader, 1, &c_str_shader.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    let mut success = i32::from(gl::FALSE);
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+    if success != i32::from(gl::TRUE) {
+        let log = shader_info_log(shader);
+        gl::DeleteShader(shader);
+        return Err(ShaderError::Compile {
+            stage: shader_type,
+            path: path.map(|p| p.to_string()),
+            lines: error_lines(&log, preprocessed),
+            log,
+        });
+    }
+    Ok(shader)
+}
+
+unsafe fn shader_info_log(shader_id: u32) -> String {
+    let mut length = 0;
+    gl::GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut length);
+    let mut info_log = vec![0u8; length.max(1) as usize];
+    gl::GetShaderInfoLog(shader_id, length, ptr::null_mut(), info_log.as_mut_ptr() as *mut gl::types::GLchar);
+    String::from_utf8_lossy(&info_log).trim_end_matches('\0').to_string()
+}
+
+unsafe fn program_info_log(program_id: u32) -> String {
+    let mut length = 0;
+    gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut length);
+    let mut info_log = vec![0u8; length.max(1) as usize];
+    gl::GetProgramInfoLog(program_id, length, ptr::null_mut(), info_log.as_mut_ptr() as *mut gl::types::GLchar);
+    String::from_utf8_lossy(&info_log).trim_end_matches('\0').to_string()
+}
+
+// List the active attributes or uniforms of a linked program, leaving out built in variables
+unsafe fn active_variables(program_id: u32, kind: gl::types::GLenum) -> Vec<ActiveVariable> {
+    let max_length_kind = if kind == gl::ACTIVE_ATTRIBUTES { gl::ACTIVE_ATTRIBUTE_MAX_LENGTH } else { gl::ACTIVE_UNIFORM_MAX_LENGTH };
+    let mut count = 0;
+    let mut max_length = 0;
+    gl::GetProgramiv(program_id, kind, &mut count);
+    gl::GetProgramiv(program_id, max_length_kind, &mut max_length);
+
+    let mut variables = vec![];
+    for index in 0..count as u32 {
+        let mut name = vec![0u8; max_length.max(1) as usize];
+        let mut length = 0;
+        let mut size = 0;
+        let mut gl_type = 0;
+        let name_ptr = name.as_mut_ptr() as *mut gl::types::GLchar;
+        if kind == gl::ACTIVE_ATTRIBUTES {
+            gl::GetActiveAttrib(program_id, index, max_length, &mut length, &mut size, &mut gl_type, name_ptr);
+        } else {
+            gl::GetActiveUniform(program_id, index, max_length, &mut length, &mut size, &mut gl_type, name_ptr);
+        }
+        name.truncate(length as usize);
+        let name = String::from_utf8_lossy(&name).to_string();
+        if name.starts_with("gl_") {
+            continue;
+        }
+
+        let c_name = CString::new(name.as_bytes()).unwrap();
+        let location = if kind == gl::ACTIVE_ATTRIBUTES {
+            gl::GetAttribLocation(program_id, c_name.as_ptr())
+        } else {
+            gl::GetUniformLocation(program_id, c_name.as_ptr())
+        };
+        variables.push(ActiveVariable { name, gl_type, size, location });
+    }
+    variables
+}
+
+fn component_count(gl_type: gl::types::GLenum) -> Option<i32> {
+    match gl_type {
+        gl::FLOAT | gl::INT | gl::UNSIGNED_INT => Some(1),
+        gl::FLOAT_VEC2 | gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 => Some(2),
+        gl::FLOAT_VEC3 | gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 => Some(3),
+        gl::FLOAT_VEC4 | gl::INT_VEC4 | gl::UNSIGNED_INT_VEC4 => Some(4),
+        _ => None,
+    }
+}
+
+// Levenshtein distance, used to suggest the uniform that was probably meant
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// Pair each line of a compiler log with the file and line it refers to
+fn error_lines(log: &str, preprocessed: &Preprocessed) -> Vec<ErrorLine> {
+    let source_lines: Vec<&str> = preprocessed.source.lines().collect();
+    log.lines().filter_map(|message| {
+        let line = log_line_number(message)?;
+        let (file, original_line) = preprocessed.origin(line).unwrap_or(("<unknown>", line));
+        Some(ErrorLine {
+            file: file.to_string(),
+            line: original_line,
+            message: message.trim().to_string(),
+            source: source_lines.get(line.wrapping_sub(1)).unwrap_or(&"").to_string(),
+        })
+    }).collect()
+}
+
+// Find the line number in a log message. Drivers use different formats:
+//   NVIDIA "0(12) : error ...", Mesa "0:12(5): error: ..." and AMD "ERROR: 0:12: ..."
+fn log_line_number(message: &str) -> Option<usize> {
+    let message = message.trim_start_matches("ERROR: ").trim_start_matches("WARNING: ");
+    let string_index_end = message.find(|c: char| !c.is_ascii_digit())?;
+    if string_index_end == 0 {
+        return None;
+    }
+    let rest = &message[string_index_end..];
+    let rest = rest.strip_prefix('(').or_else(|| rest.strip_prefix(':'))?;
+    let line_end = rest.find(|c: char| !c.is_ascii_digit())?;
+    rest[..line_end].parse().ok()
+}
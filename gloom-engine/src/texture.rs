@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use gl::types::{GLenum, GLint};
+use image::codecs::hdr::HdrDecoder;
+use image::{DynamicImage, GenericImageView, Rgb};
+use crate::gl_object::{Handle, Texture};
+use crate::shader::{Sampler, Shader};
+use crate::util;
+use crate::watcher;
+
+// From EXT_texture_filter_anisotropic, which became core in OpenGL 4.6. The gl crate only generates bindings for 4.5.
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+// The format a texture is stored in on the GPU. Images are converted to it when they are decoded, so e.g. a grayscale
+// image can be loaded as Rgb8 and a color image as R8.
+// The sRGB formats are for color textures, and are converted to linear values when sampled.
+// The float formats keep the full range of .hdr images. Other images are converted to them from their 16 bit channels,
+// so they only hold values between 0 and 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    R8,
+    Rgb8,
+    Rgba8,
+    Srgb8,
+    Srgba8,
+    R16,
+    Rgb16,
+    Rgba16,
+    R32F,
+    Rgb32F,
+    Rgba32F,
+}
+
+impl TextureFormat {
+    // The internal format, pixel format and pixel type to pass to TexImage2D
+    fn gl_formats(self) -> (GLint, GLenum, GLenum) {
+        match self {
+            TextureFormat::R8 => (gl::R8 as GLint, gl::RED, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgb8 => (gl::RGB8 as GLint, gl::RGB, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgba8 => (gl::RGBA8 as GLint, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::Srgb8 => (gl::SRGB8 as GLint, gl::RGB, gl::UNSIGNED_BYTE),
+            TextureFormat::Srgba8 => (gl::SRGB8_ALPHA8 as GLint, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::R16 => (gl::R16 as GLint, gl::RED, gl::UNSIGNED_SHORT),
+            TextureFormat::Rgb16 => (gl::RGB16 as GLint, gl::RGB, gl::UNSIGNED_SHORT),
+            TextureFormat::Rgba16 => (gl::RGBA16 as GLint, gl::RGBA, gl::UNSIGNED_SHORT),
+            TextureFormat::R32F => (gl::R32F as GLint, gl::RED, gl::FLOAT),
+            TextureFormat::Rgb32F => (gl::RGB32F as GLint, gl::RGB, gl::FLOAT),
+            TextureFormat::Rgba32F => (gl::RGBA32F as GLint, gl::RGBA, gl::FLOAT),
+        }
+    }
+
+    // Number of channels per pixel and bytes per channel
+    fn pixel_layout(self) -> (usize, usize) {
+        match self {
+            TextureFormat::R8 => (1, 1),
+            TextureFormat::Rgb8 | TextureFormat::Srgb8 => (3, 1),
+            TextureFormat::Rgba8 | TextureFormat::Srgba8 => (4, 1),
+            TextureFormat::R16 => (1, 2),
+            TextureFormat::Rgb16 => (3, 2),
+            TextureFormat::Rgba16 => (4, 2),
+            TextureFormat::R32F => (1, 4),
+            TextureFormat::Rgb32F => (3, 4),
+            TextureFormat::Rgba32F => (4, 4),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl Wrap {
+    fn gl_enum(self) -> GLenum {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+// How a texture is stored and sampled. The defaults are RGB8, repeating, linear filtering between mipmaps and no
+// anisotropic filtering.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureOptions {
+    pub format: TextureFormat,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mipmaps: bool,
+    // Maximum number of anisotropic samples, where 1 turns anisotropic filtering off.
+    // Clamped to what the driver supports.
+    pub anisotropy: u32,
+}
+
+impl Default for TextureOptions {
+    fn default() -> TextureOptions {
+        TextureOptions {
+            format: TextureFormat::Rgb8,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmaps: true,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl TextureOptions {
+    // Set the sampler state of the texture bound to target
+    unsafe fn apply(&self, target: GLenum) {
+        let min_filter = match (self.min_filter, self.mipmaps) {
+            (Filter::Nearest, false) => gl::NEAREST,
+            (Filter::Linear, false) => gl::LINEAR,
+            (Filter::Nearest, true) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, true) => gl::LINEAR_MIPMAP_LINEAR,
+        };
+        let mag_filter = match self.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        };
+
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_S, self.wrap_s.gl_enum() as GLint);
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_T, self.wrap_t.gl_enum() as GLint);
+        gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+        gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+
+        if self.anisotropy > 1 && anisotropy_supported() {
+            let mut max_anisotropy = 1.0;
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+            gl::TexParameterf(target, TEXTURE_MAX_ANISOTROPY, (self.anisotropy as f32).min(max_anisotropy));
+        }
+    }
+}
+
+unsafe fn anisotropy_supported() -> bool {
+    util::has_extension("GL_EXT_texture_filter_anisotropic") || util::has_extension("GL_ARB_texture_filter_anisotropic")
+}
+
+// A decoded image, converted to the format it is uploaded in. Unlike a texture, it can be sent between threads,
+// so images can be decoded on the loader workers.
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub pixels: Vec<u8>,
+}
+
+impl TextureData {
+    pub fn load(path: &str, format: TextureFormat) -> Result<TextureData, String> {
+        let float = matches!(format, TextureFormat::R32F | TextureFormat::Rgb32F | TextureFormat::Rgba32F);
+        let hdr = Path::new(path).extension().map_or(false, |e| e.eq_ignore_ascii_case("hdr"));
+        if float && hdr {
+            return TextureData::load_hdr(path, format);
+        }
+        image::open(path)
+            .map(|img| TextureData::from_image(&img, format))
+            .map_err(|e| format!("Failed to load image {}: {}", path, e))
+    }
+
+    // The image crate decodes HDR images to 8 bits per channel, so they are read as floats by HdrDecoder instead
+    fn load_hdr(path: &str, format: TextureFormat) -> Result<TextureData, String> {
+        let (width, height, pixels) = read_hdr(path)?;
+        let channels: Vec<f32> = match format {
+            // The same weights as the image crate uses for grayscale
+            TextureFormat::R32F => pixels.iter().map(|&Rgb([r, g, b])| 0.2126 * r + 0.7152 * g + 0.0722 * b).collect(),
+            TextureFormat::Rgb32F => pixels.iter().flat_map(|&Rgb(rgb)| rgb.to_vec()).collect(),
+            TextureFormat::Rgba32F => pixels.iter().flat_map(|&Rgb([r, g, b])| vec![r, g, b, 1.0]).collect(),
+            _ => unreachable!("{:?} is not a float format", format),
+        };
+        Ok(TextureData { width, height, format, pixels: to_bytes(&channels, f32::to_ne_bytes) })
+    }
+
+    pub fn from_image(img: &DynamicImage, format: TextureFormat) -> TextureData {
+        let (width, height) = (img.width(), img.height());
+        let pixels = match format {
+            TextureFormat::R8 => img.to_luma8().into_raw(),
+            TextureFormat::Rgb8 | TextureFormat::Srgb8 => img.to_rgb8().into_raw(),
+            TextureFormat::Rgba8 | TextureFormat::Srgba8 => img.to_rgba8().into_raw(),
+            TextureFormat::R16 => to_bytes(&img.to_luma16().into_raw(), |v| v.to_ne_bytes()),
+            TextureFormat::Rgb16 => to_bytes(&img.to_rgb16().into_raw(), |v| v.to_ne_bytes()),
+            TextureFormat::Rgba16 => to_bytes(&img.to_rgba16().into_raw(), |v| v.to_ne_bytes()),
+            TextureFormat::R32F => to_bytes(&img.to_luma16().into_raw(), to_float_bytes),
+            TextureFormat::Rgb32F => to_bytes(&img.to_rgb16().into_raw(), to_float_bytes),
+            TextureFormat::Rgba32F => to_bytes(&img.to_rgba16().into_raw(), to_float_bytes),
+        };
+        TextureData { width, height, format, pixels }
+    }
+
+    // Replace the image of a texture, keeping the texture object so everything holding it sees the new image.
+    pub unsafe fn upload(&self, texture: &Texture, options: &TextureOptions) {
+        let (internal_format, format, pixel_type) = self.format.gl_formats();
+
+        texture.bind(0);
+        // Rows of RGB8 and R8 images aren't padded to 4 bytes
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(texture.target, 0, internal_format, self.width as i32, self.height as i32, 0, format, pixel_type,
+                       util::pointer_to_array(&self.pixels));
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+        if options.mipmaps {
+            gl::GenerateMipmap(texture.target);
+        }
+        options.apply(texture.target);
+    }
+}
+
+fn to_bytes<T: Copy, B: AsRef<[u8]>>(values: &[T], convert: impl Fn(T) -> B) -> Vec<u8> {
+    values.iter().flat_map(|&v| convert(v).as_ref().to_vec()).collect()
+}
+
+fn to_float_bytes(value: u16) -> [u8; 4] {
+    (value as f32 / u16::MAX as f32).to_ne_bytes()
+}
+
+// The width, height and linear colors of a Radiance HDR image
+fn read_hdr(path: &str) -> Result<(u32, u32, Vec<Rgb<f32>>), String> {
+    let error = |e: &dyn std::fmt::Display| format!("Failed to load HDR image {}: {}", path, e);
+    let file = File::open(path).map_err(|e| error(&e))?;
+    let decoder = HdrDecoder::new(BufReader::new(file)).map_err(|e| error(&e))?;
+    let (width, height) = (decoder.metadata().width, decoder.metadata().height);
+    let pixels = decoder.read_image_hdr().map_err(|e| error(&e))?;
+    Ok((width, height, pixels))
+}
+
+// The six faces of a cube map, square images of the same size in the order of the GL face targets: +X, -X, +Y, -Y, +Z
+// and -Z. The first row of each face is its top as seen from inside the cube, so the faces aren't flipped.
+pub struct CubemapData {
+    pub size: u32,
+    pub format: TextureFormat,
+    pub faces: Vec<Vec<u8>>,
+}
+
+impl CubemapData {
+    // Load a cube map from one image per face, in the order of the face targets
+    pub fn load_faces(paths: &[&str; 6], format: TextureFormat) -> Result<CubemapData, String> {
+        let faces = paths.iter().map(|path| TextureData::load(path, format)).collect::<Result<Vec<TextureData>, String>>()?;
+        let size = faces[0].width;
+        if let Some((path, face)) = paths.iter().zip(&faces).find(|(_, face)| face.width != size || face.height != size) {
+            return Err(format!("Cube map face {} is {}x{}, but has to be {}x{} like the first face", path, face.width, face.height, size, size));
+        }
+        Ok(CubemapData { size, format, faces: faces.into_iter().map(|face| face.pixels).collect() })
+    }
+
+    // Load an equirectangular HDR panorama and project it onto faces of size by size texels.
+    // The faces are stored as Rgb32F to keep the range of the image.
+    pub fn load_equirectangular(path: &str, size: u32) -> Result<CubemapData, String> {
+        let (width, height, pixels) = read_hdr(path)?;
+        let (width, height) = (width as i64, height as i64);
+
+        // Bilinear lookup in the panorama, wrapping around horizontally
+        let texel = |x: i64, y: i64| pixels[(y.max(0).min(height - 1) * width + x.rem_euclid(width)) as usize].0;
+        let sample = |(x, y, z): (f32, f32, f32)| {
+            let length = (x * x + y * y + z * z).sqrt();
+            let u = 0.5 + z.atan2(x) / (2.0 * PI);
+            let v = (y / length).acos() / PI;
+            let (fx, fy) = (u * width as f32 - 0.5, v * height as f32 - 0.5);
+            let (x0, y0) = (fx.floor() as i64, fy.floor() as i64);
+            let (tx, ty) = (fx - fx.floor(), fy - fy.floor());
+            let (a, b, c, d) = (texel(x0, y0), texel(x0 + 1, y0), texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+            let mut color = [0.0; 3];
+            for i in 0..3 {
+                let top = a[i] + (b[i] - a[i]) * tx;
+                let bottom = c[i] + (d[i] - c[i]) * tx;
+                color[i] = top + (bottom - top) * ty;
+            }
+            color
+        };
+
+        let faces = (0..6).map(|face| {
+            let mut bytes = Vec::with_capacity((size * size * 3 * 4) as usize);
+            for y in 0..size {
+                for x in 0..size {
+                    let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    for channel in &sample(cube_face_direction(face, s, t)) {
+                        bytes.extend_from_slice(&channel.to_ne_bytes());
+                    }
+                }
+            }
+            bytes
+        }).collect();
+        Ok(CubemapData { size, format: TextureFormat::Rgb32F, faces })
+    }
+
+    // Replace the faces of a TEXTURE_CUBE_MAP texture. The wrapping of t is also used for r.
+    pub unsafe fn upload(&self, texture: &Texture, options: &TextureOptions) {
+        let (internal_format, format, pixel_type) = self.format.gl_formats();
+
+        texture.bind(0);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        for (i, face) in self.faces.iter().enumerate() {
+            gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum, 0, internal_format, self.size as i32, self.size as i32,
+                           0, format, pixel_type, util::pointer_to_array(face));
+        }
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+        if options.mipmaps {
+            gl::GenerateMipmap(texture.target);
+        }
+        options.apply(texture.target);
+        gl::TexParameteri(texture.target, gl::TEXTURE_WRAP_R, options.wrap_t.gl_enum() as GLint);
+    }
+
+    // Average color around the horizon, along the middle rows of the side faces, as the shaders see it when sampling.
+    // Used to fade distant geometry into the sky.
+    pub fn horizon_color(&self) -> [f32; 3] {
+        let (channels, channel_size) = self.format.pixel_layout();
+        let srgb = self.format == TextureFormat::Srgb8 || self.format == TextureFormat::Srgba8;
+        let channel = |bytes: &[u8]| {
+            let value = match channel_size {
+                1 => bytes[0] as f32 / u8::MAX as f32,
+                2 => u16::from_ne_bytes([bytes[0], bytes[1]]) as f32 / u16::MAX as f32,
+                _ => f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            };
+            if srgb { srgb_to_linear(value) } else { value }
+        };
+
+        let pixel_size = channels * channel_size;
+        let row_size = self.size as usize * pixel_size;
+        let middle = self.size as usize / 2 * row_size;
+        let mut sum = [0.0; 3];
+        for face in &[0, 1, 4, 5] {
+            for pixel in self.faces[*face][middle..middle + row_size].chunks_exact(pixel_size) {
+                for (i, total) in sum.iter_mut().enumerate() {
+                    // Grayscale formats have the same value in every channel
+                    let offset = i.min(channels - 1) * channel_size;
+                    *total += channel(&pixel[offset..offset + channel_size]);
+                }
+            }
+        }
+        let count = 4.0 * self.size as f32;
+        [sum[0] / count, sum[1] / count, sum[2] / count]
+    }
+
+    // Number of mipmap levels of the uploaded texture when mipmaps are generated
+    pub fn levels(&self) -> i32 {
+        32 - self.size.leading_zeros() as i32
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+// The direction through texel (s, t) of a cube map face, with s and t from -1 to 1
+fn cube_face_direction(face: usize, s: f32, t: f32) -> (f32, f32, f32) {
+    match face {
+        0 => (1.0, -t, -s),
+        1 => (-1.0, -t, s),
+        2 => (s, 1.0, t),
+        3 => (s, -1.0, -t),
+        4 => (s, -t, 1.0),
+        _ => (-s, -t, -1.0),
+    }
+}
+
+// Create a texture from an image file, decoding it on the current thread.
+pub unsafe fn load(path: &str, options: &TextureOptions) -> Result<Texture, String> {
+    let data = TextureData::load(path, options.format)?;
+    let texture = Texture::new(gl::TEXTURE_2D);
+    data.upload(&texture, options);
+    Ok(texture)
+}
+
+// Textures by file and options, so nodes using the same image share one texture.
+// Textures stay in the cache until it is cleared, even if no node uses them anymore.
+pub struct TextureCache {
+    textures: HashMap<(PathBuf, TextureOptions), Handle<Texture>>,
+}
+
+impl TextureCache {
+    pub fn new() -> TextureCache {
+        TextureCache { textures: HashMap::new() }
+    }
+
+    pub fn get(&self, path: &str, options: &TextureOptions) -> Option<Handle<Texture>> {
+        self.textures.get(&(watcher::canonical(path.as_ref()), *options)).cloned()
+    }
+
+    pub fn insert(&mut self, path: &str, options: &TextureOptions, texture: Handle<Texture>) {
+        self.textures.insert((watcher::canonical(path.as_ref()), *options), texture);
+    }
+
+    // The cached texture for path, or a new one loaded from it and added to the cache
+    pub unsafe fn get_or_load(&mut self, path: &str, options: &TextureOptions) -> Result<Handle<Texture>, String> {
+        if let Some(texture) = self.get(path, options) {
+            return Ok(texture);
+        }
+        let texture = Handle::new(load(path, options)?);
+        self.insert(path, options, Handle::clone(&texture));
+        Ok(texture)
+    }
+
+    // Drop the cache's references to its textures. Textures still used by a node are deleted with the last node using them.
+    pub fn clear(&mut self) {
+        self.textures.clear();
+    }
+}
+
+// Sampler uniform names of the standard texture slots
+pub const DIFFUSE: &str = "diffuse_texture";
+pub const NORMAL: &str = "normal_texture";
+pub const SPECULAR: &str = "specular_texture";
+pub const EMISSIVE: &str = "emissive_texture";
+pub const BASE_COLOR: &str = "base_color_texture";
+pub const METALLIC: &str = "metallic_texture";
+pub const ROUGHNESS: &str = "roughness_texture";
+
+// Textures by slot name. When bound, the slots are assigned consecutive texture units in the order they were added,
+// and the sampler uniform named after each slot is set to its unit.
+#[derive(Clone, Default)]
+pub struct TextureSet {
+    slots: Vec<(String, Handle<Texture>)>,
+}
+
+impl TextureSet {
+    pub fn new() -> TextureSet {
+        TextureSet { slots: vec![] }
+    }
+
+    // Put a texture in a slot, replacing the texture already there
+    pub fn set(&mut self, slot: &str, texture: Handle<Texture>) {
+        match self.slots.iter_mut().find(|(name, _)| name == slot) {
+            Some(entry) => entry.1 = texture,
+            None => self.slots.push((slot.to_string(), texture)),
+        }
+    }
+
+    pub fn get(&self, slot: &str) -> Option<&Handle<Texture>> {
+        self.slots.iter().find(|(name, _)| name == slot).map(|(_, texture)| texture)
+    }
+
+    pub fn remove(&mut self, slot: &str) {
+        self.slots.retain(|(name, _)| name != slot);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // Bind the textures to consecutive units starting at first_unit, and set the sampler uniforms of the shader,
+    // which has to be active. Returns the unit after the last one used.
+    pub unsafe fn bind(&self, shader: &Shader, first_unit: u32) -> u32 {
+        let mut unit = first_unit;
+        for (name, texture) in &self.slots {
+            texture.bind(unit);
+            shader.set_uniform(name, &Sampler(unit as i32));
+            unit += 1;
+        }
+        unit
+    }
+}
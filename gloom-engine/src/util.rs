@@ -0,0 +1,104 @@
+use core::ffi::c_void;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use core::mem;
+use std::time::{UNIX_EPOCH, SystemTime};
+
+pub unsafe fn get_gl_string(name: gl::types::GLenum) -> String {
+    CStr::from_ptr(gl::GetString(name) as *const c_char).to_string_lossy().to_string()
+}
+
+thread_local! {
+    // Extensions of the context that is current on this thread, read by the first call to has_extension
+    static EXTENSIONS: RefCell<Option<HashSet<String>>> = RefCell::new(None);
+}
+
+// Whether the current context supports the named extension, e.g. "GL_EXT_texture_filter_anisotropic".
+// The extensions are only listed once per thread, as every thread only ever has one context.
+pub unsafe fn has_extension(name: &str) -> bool {
+    EXTENSIONS.with(|extensions| {
+        extensions.borrow_mut().get_or_insert_with(|| {
+            let mut count = 0;
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+            (0..count as u32)
+                .map(|i| CStr::from_ptr(gl::GetStringi(gl::EXTENSIONS, i) as *const c_char).to_string_lossy().to_string())
+                .collect()
+        }).contains(name)
+    })
+}
+
+// Debug callback to panic upon enountering any OpenGL error
+pub extern "system" fn debug_callback(
+    source: u32, e_type: u32, id: u32,
+    severity: u32, _length: i32,
+    msg: *const c_char, _data: *mut std::ffi::c_void
+) {
+    if e_type != gl::DEBUG_TYPE_ERROR { return }
+    if severity == gl::DEBUG_SEVERITY_HIGH ||
+       severity == gl::DEBUG_SEVERITY_MEDIUM ||
+       severity == gl::DEBUG_SEVERITY_LOW
+    {
+        let severity_string = match severity {
+            gl::DEBUG_SEVERITY_HIGH => "high",
+            gl::DEBUG_SEVERITY_MEDIUM => "medium",
+            gl::DEBUG_SEVERITY_LOW => "low",
+            _ => "unknown",
+        };
+        unsafe {
+            // The message belongs to the driver, so it is only borrowed
+            let error_message = CStr::from_ptr(msg).to_string_lossy().to_string();
+            panic!("{}: Error of severity {} raised from {}: {}\n",
+                id, severity_string, source, error_message);
+        }
+    }
+}
+
+
+// Get the OpenGL-compatible pointer to an arbitrary array of numbers.
+// Empty arrays give a null pointer, which OpenGL accepts along with a size of 0.
+pub fn pointer_to_array<T>(val: &[T]) -> *const c_void {
+    if val.is_empty() {
+        std::ptr::null()
+    } else {
+        val.as_ptr() as *const c_void
+    }
+}
+
+// == // Helper functions to make interacting with OpenGL a little bit prettier. You *WILL* need these! // == //
+// The names should be pretty self explanatory
+pub fn byte_size_of_array<T>(val: &[T]) -> isize {
+    std::mem::size_of_val(&val[..]) as isize
+}
+
+// Get the size of the given type in bytes
+pub fn size_of<T>() -> i32 {
+    mem::size_of::<T>() as i32
+}
+
+// Get an offset in bytes for n units of type T
+pub fn offset<T>(n: u32) -> *const c_void {
+    (n * mem::size_of::<T>() as u32) as *const T as *const c_void
+}
+
+pub fn generate_rng(min: f32, max: f32) -> f32 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .subsec_nanos() as f32;
+
+    ((nanos % 255.0) / 255.0) * (max - min) + min
+}
+
+pub const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// FNV-1a hash, used for cache keys over std's hasher because its output has to stay the same between builds
+pub fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
\ No newline at end of file
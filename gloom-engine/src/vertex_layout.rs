@@ -0,0 +1,126 @@
+extern crate nalgebra_glm as glm;
+
+use crate::gl_object::Pod;
+
+// Vertex layouts describe how the fields of a #[repr(C)] vertex struct map to shader attributes, so the struct is the only
+// place that has to change when an attribute is added. Buffers are filled with the structs as they are, and the
+// VertexAttribPointer calls are generated from the layout.
+//
+//     #[repr(C)]
+//     #[derive(Copy, Clone)]
+//     pub struct MeshVertex { pub position: glm::Vec3, pub color: glm::Vec4 }
+//     unsafe impl Pod for MeshVertex {}
+//     vertex_layout!(MeshVertex { position => "in_position" @ 0, color => "in_color" @ 1 });
+
+// An attribute of a vertex struct: which shader input it feeds, and where it is in the struct
+#[derive(Debug, Copy, Clone)]
+pub struct VertexAttribute {
+    pub name: &'static str,
+    pub location: u32,
+    pub components: i32,
+    pub gl_type: gl::types::GLenum,
+    pub integer: bool,
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    // Describe the field at field_ptr of the vertex at base_ptr. Used by vertex_layout!, which gets the pointers without
+    // creating a vertex.
+    pub fn new<T: AttributeType>(name: &'static str, location: u32, base_ptr: usize, field_ptr: *const T) -> VertexAttribute {
+        VertexAttribute {
+            name,
+            location,
+            components: T::COMPONENTS,
+            gl_type: T::GL_TYPE,
+            integer: T::INTEGER,
+            offset: field_ptr as usize - base_ptr,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VertexLayout {
+    pub stride: i32,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    // Point the attributes at the buffer bound to ARRAY_BUFFER. A divisor of 0 advances the attributes per vertex,
+    // and a divisor of n per n instances.
+    pub unsafe fn enable(&self, divisor: u32) {
+        for attribute in &self.attributes {
+            let offset = attribute.offset as *const gl::types::GLvoid;
+            gl::EnableVertexAttribArray(attribute.location);
+            if attribute.integer {
+                gl::VertexAttribIPointer(attribute.location, attribute.components, attribute.gl_type, self.stride, offset);
+            } else {
+                gl::VertexAttribPointer(attribute.location, attribute.components, attribute.gl_type, gl::FALSE, self.stride, offset);
+            }
+            gl::VertexAttribDivisor(attribute.location, divisor);
+        }
+    }
+}
+
+// Vertex structs, with their layout generated by vertex_layout!
+pub trait Vertex: Pod {
+    fn layout() -> VertexLayout;
+}
+
+// Field types that can be used as vertex attributes
+pub trait AttributeType {
+    const COMPONENTS: i32;
+    const GL_TYPE: gl::types::GLenum;
+    const INTEGER: bool;
+}
+
+macro_rules! impl_attribute_type {
+    ($type:ty, $components:expr, $gl_type:expr, $integer:expr) => {
+        impl AttributeType for $type {
+            const COMPONENTS: i32 = $components;
+            const GL_TYPE: gl::types::GLenum = $gl_type;
+            const INTEGER: bool = $integer;
+        }
+    };
+}
+
+impl_attribute_type!(f32, 1, gl::FLOAT, false);
+impl_attribute_type!([f32; 2], 2, gl::FLOAT, false);
+impl_attribute_type!([f32; 3], 3, gl::FLOAT, false);
+impl_attribute_type!([f32; 4], 4, gl::FLOAT, false);
+impl_attribute_type!(glm::Vec2, 2, gl::FLOAT, false);
+impl_attribute_type!(glm::Vec3, 3, gl::FLOAT, false);
+impl_attribute_type!(glm::Vec4, 4, gl::FLOAT, false);
+impl_attribute_type!(i32, 1, gl::INT, true);
+impl_attribute_type!([i32; 2], 2, gl::INT, true);
+impl_attribute_type!([i32; 3], 3, gl::INT, true);
+impl_attribute_type!([i32; 4], 4, gl::INT, true);
+impl_attribute_type!(u32, 1, gl::UNSIGNED_INT, true);
+impl_attribute_type!([u32; 2], 2, gl::UNSIGNED_INT, true);
+impl_attribute_type!([u32; 3], 3, gl::UNSIGNED_INT, true);
+impl_attribute_type!([u32; 4], 4, gl::UNSIGNED_INT, true);
+
+// Implement Vertex for a #[repr(C)] struct, mapping each listed field to a named attribute at a location.
+// Fields that aren't listed are still uploaded, but not passed to the shader. The struct has to implement Pod, which is
+// left to the struct itself since only its author can tell that it has no padding.
+#[macro_export]
+macro_rules! vertex_layout {
+    ($vertex:ty { $($field:ident => $name:literal @ $location:literal),* $(,)? }) => {
+        impl $crate::vertex_layout::Vertex for $vertex {
+            fn layout() -> $crate::vertex_layout::VertexLayout {
+                let vertex = std::mem::MaybeUninit::<$vertex>::uninit();
+                let base_ptr = vertex.as_ptr();
+                $crate::vertex_layout::VertexLayout {
+                    stride: std::mem::size_of::<$vertex>() as i32,
+                    attributes: vec![$(
+                        $crate::vertex_layout::VertexAttribute::new(
+                            $name,
+                            $location,
+                            base_ptr as usize,
+                            unsafe { std::ptr::addr_of!((*base_ptr).$field) },
+                        )
+                    ),*],
+                }
+            }
+        }
+    };
+}
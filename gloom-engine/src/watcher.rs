@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// Polls a directory and its subdirectories for files with one of the given extensions that were created or modified since the last poll.
+pub struct FileWatcher {
+    directory: PathBuf,
+    extensions: Vec<String>,
+    modified: HashMap<PathBuf, SystemTime>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(directory: &str, extensions: &[&str]) -> FileWatcher {
+        let mut watcher = FileWatcher {
+            directory: PathBuf::from(directory),
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            modified: HashMap::new(),
+            interval: Duration::from_millis(500),
+            last_poll: Instant::now(),
+        };
+        watcher.modified = watcher.scan();
+        watcher
+    }
+
+    // The files that changed since the last call. Only touches the file system every half second.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+
+        let modified = self.scan();
+        let changed = modified.iter()
+            .filter(|&(path, time)| self.modified.get(path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.modified = modified;
+        changed
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut modified = HashMap::new();
+        self.scan_directory(&self.directory, &mut modified);
+        modified
+    }
+
+    fn scan_directory(&self, directory: &Path, modified: &mut HashMap<PathBuf, SystemTime>) {
+        if let Ok(entries) = fs::read_dir(directory) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                // Symbolic links to directories aren't followed, so a link cycle can't make the scan loop forever
+                if entry.file_type().map_or(false, |t| t.is_dir()) {
+                    self.scan_directory(&path, modified);
+                    continue;
+                }
+                let watched = path.extension()
+                    .and_then(|e| e.to_str())
+                    .map_or(false, |e| self.extensions.iter().any(|x| x == e));
+                if let (true, Ok(time)) = (watched, fs::metadata(&path).and_then(|m| m.modified())) {
+                    modified.insert(canonical(&path), time);
+                }
+            }
+        }
+    }
+}
+
+// The absolute path of a file, so that paths given in different ways can be compared.
+pub fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or(path.to_path_buf())
+}